@@ -50,9 +50,10 @@ async fn main() {
     let assets = cwd.join(".minecraft").join("assets");
     info!("Assets directory: {}", assets.display());
 
-    let index = manifest.asset_index().download().await.unwrap();
+    let client = reqwest::Client::new();
+    let index = manifest.asset_index().save(&assets, &client).await.unwrap();
 
-    let mut download_index = AssetDownloader::new(index, assets, reqwest::Client::new(), 16);
+    let mut download_index = AssetDownloader::new(index, assets, client, 16);
     let mut reciever = download_index.create_channel();
 
     info!("Downloaded asset index");
@@ -85,6 +86,15 @@ async fn main() {
                 bar.finish();
                 m.remove(&bar);
             }
+            DownloadMessage::Failed(object, report) => {
+                if let Some((_, bar)) = bars.remove(object.hash()) {
+                    bar.abandon();
+                    m.remove(&bar);
+                }
+
+                m.println(format!("Failed to download {}: {report:?}", object.hash()))
+                    .unwrap();
+            }
             DownloadMessage::DownloadedAll => {
                 m.println("Downloaded all objects. Joining tasks").unwrap();
                 // we're done, make sure to break
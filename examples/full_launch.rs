@@ -168,6 +168,7 @@ async fn main() {
         .game_directory(minecraft_dir.clone())
         .assets_directory(minecraft_dir.join("assets"))
         .libraries_directory(minecraft_dir.join("libraries"))
+        .natives_directory(manifest_dir.join("natives"))
         .version_manifest_path(manifest_dir.join(format!("{}.json", latest.id())))
         .is_snapshot(false)
         .version_name("Vanilla")
@@ -186,7 +187,11 @@ async fn main() {
 
     info!("Launcher created. Downloading files");
 
-    let index = manifest.asset_index().download().await.unwrap();
+    let index = manifest
+        .asset_index()
+        .save(launcher.assets_directory(), &reqwest::Client::new())
+        .await
+        .unwrap();
 
     let mut downloader =
         launcher::Downloader::new(&launcher, index, manifest.libraries().to_vec(), 16);
@@ -214,6 +219,7 @@ async fn main() {
                     launcher::DownloadItem::Asset(asset) => asset.hash().to_owned(),
                     launcher::DownloadItem::Library(library) => library.sha1().to_owned(),
                     launcher::DownloadItem::Client(_) => "client".to_owned(),
+                    launcher::DownloadItem::Logging(file) => file.sha1().to_owned(),
                 };
 
                 let Some((_, bar)): Option<(String, ProgressBar)> = bars.remove(&hash) else {
@@ -224,6 +230,10 @@ async fn main() {
                 m.remove(&bar);
                 m.println(format!("Downloaded {}", hash)).unwrap();
             }
+            DownloadMessage::Failed(object, report) => {
+                m.println(format!("Failed to download {object:?}: {report:?}"))
+                    .unwrap();
+            }
             DownloadMessage::DownloadedAll => {
                 m.println("Downloaded all objects. Joining tasks").unwrap();
                 // we're done, make sure to break
@@ -234,12 +244,14 @@ async fn main() {
                     launcher::DownloadItem::Asset(ref asset) => asset.hash().to_owned(),
                     launcher::DownloadItem::Library(ref library) => library.sha1().to_owned(),
                     launcher::DownloadItem::Client(_) => "client".to_owned(),
+                    launcher::DownloadItem::Logging(ref file) => file.sha1().to_owned(),
                 };
 
                 let size = match object {
                     launcher::DownloadItem::Asset(asset) => asset.size(),
-                    launcher::DownloadItem::Library(library) => library.size(),
+                    launcher::DownloadItem::Library(library) => library.size().unwrap_or_default(),
                     launcher::DownloadItem::Client(client) => client.size(),
+                    launcher::DownloadItem::Logging(file) => file.size(),
                 };
 
                 let bar = bars.entry(hash.clone()).or_insert_with(|| {
@@ -1,17 +1,63 @@
 use error_stack::{IntoReport, Result, ResultExt};
-use futures::{stream, StreamExt, TryStreamExt};
+use glob::{MatchOptions, Pattern};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::{cmp::min, collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
 use tokio::{
-    io::AsyncWriteExt,
+    fs,
     sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
 };
 
-use crate::downloader::{DownloadError, DownloadMessage, Downloader};
+use crate::downloader::{
+    download_streamed, remove_stale_temp_files, DownloadError, DownloadMessage, DownloadSummary,
+    Downloader, RetryPolicy,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Assets {
     objects: HashMap<String, Object>,
+    /// Set by the `legacy` index (1.6), whose assets are read from `assets/virtual/legacy`
+    #[serde(
+        default,
+        rename = "virtual",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    is_virtual: bool,
+    /// Set by the `pre-1.6` index, whose assets are read from `<game_dir>/resources`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    map_to_resources: bool,
+}
+
+/// Where the game reads its assets from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetLayout {
+    /// Straight from `assets/objects`, by hash (1.7 onwards)
+    Objects,
+    /// From a copy of the objects by name in `assets/virtual/<index id>`
+    Virtual,
+    /// From a copy of the objects by name in `<game_dir>/resources`
+    Resources,
+}
+
+impl AssetLayout {
+    /// Gets the directory the game reads assets from with this layout, used for `${game_assets}`
+    #[must_use]
+    pub fn game_assets_directory(
+        self,
+        index_id: &str,
+        assets_directory: &Path,
+        game_directory: &Path,
+    ) -> PathBuf {
+        match self {
+            Self::Objects => assets_directory.to_path_buf(),
+            Self::Virtual => assets_directory.join("virtual").join(index_id),
+            Self::Resources => game_directory.join("resources"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,11 +69,13 @@ pub struct AssetDownloader {
     sender: Option<UnboundedSender<DownloadMessage<Object>>>,
 
     max_concurrent_downloads: usize,
+    retry_policy: RetryPolicy,
+    continue_on_error: bool,
 }
 
 impl AssetDownloader {
     #[must_use]
-    pub const fn new(
+    pub fn new(
         assets: Assets,
         assets_directory: PathBuf,
         client: reqwest::Client,
@@ -39,8 +87,24 @@ impl AssetDownloader {
             client,
             sender: None,
             max_concurrent_downloads,
+            retry_policy: RetryPolicy::default(),
+            continue_on_error: false,
         }
     }
+
+    /// Sets how failed downloads are retried
+    #[must_use]
+    pub const fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Keeps downloading the other assets when one fails, see [`DownloadSummary`]
+    #[must_use]
+    pub const fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
 }
 
 impl Downloader for AssetDownloader {
@@ -53,17 +117,12 @@ impl Downloader for AssetDownloader {
     }
 
     async fn download(&self, item: Self::DownloadItem) -> Result<(), DownloadError> {
-        let path = self
-            .assets_directory
-            .join(item.hash_start())
-            .join(&item.hash);
-
-        item.download(
-            path,
-            &self.client,
-            self.sender.as_ref().ok_or(DownloadError::ChannelError)?,
-        )
-        .await?;
+        let path = item.path(&self.assets_directory);
+        let sender = self.sender.as_ref().ok_or(DownloadError::ChannelError)?;
+
+        self.retry_policy
+            .run(|| item.download(path.clone(), &self.client, sender))
+            .await?;
 
         self.sender
             .as_ref()
@@ -73,33 +132,258 @@ impl Downloader for AssetDownloader {
             .change_context(DownloadError::ChannelError)
     }
 
-    async fn download_all(self: Arc<Self>) -> Result<(), DownloadError> {
+    async fn download_all(
+        self: Arc<Self>,
+    ) -> Result<DownloadSummary<Self::DownloadItem>, DownloadError> {
         let new_self = self.clone();
-        let tasks = stream::iter(self.assets.objects.values().cloned())
-            .map(|object| {
-                let cloned_self = self.clone();
-                tokio::spawn(async move { cloned_self.download(object).await })
-            })
-            .buffer_unordered(self.max_concurrent_downloads);
 
-        tasks
-            .try_collect::<Vec<_>>()
-            .await
-            .into_report()
-            .change_context(DownloadError::JoinError)?
-            .into_iter()
-            .collect::<Result<_, _>>()?;
+        remove_stale_temp_files(
+            self.assets
+                .objects
+                .values()
+                .map(|object| object.path(&self.assets_directory)),
+        )
+        .await;
 
-        new_self
-            .sender
-            .clone()
-            .ok_or(DownloadError::ChannelError)?
+        let tasks = self.assets.objects.values().cloned().map(|object| {
+            let cloned_self = self.clone();
+
+            async move {
+                let result = cloned_self.download(object.clone()).await;
+                [(object, result)]
+            }
+        });
+
+        let sender = new_self.sender.clone().ok_or(DownloadError::ChannelError)?;
+
+        let summary = DownloadSummary::collect_tasks(
+            tasks,
+            self.max_concurrent_downloads,
+            self.continue_on_error,
+            &sender,
+        )
+        .await?;
+
+        sender
             .send(DownloadMessage::DownloadedAll)
             .into_report()
             .change_context(DownloadError::ChannelError)?;
 
+        Ok(summary)
+    }
+}
+
+impl Assets {
+    /// Loads an asset index saved by [`crate::assets::client::AssetIndex::save`]
+    ///
+    /// # Errors
+    /// Errors if the index cannot be read or parsed
+    pub async fn load(path: &Path) -> Result<Self, DownloadError> {
+        let contents = fs::read(path)
+            .await
+            .into_report()
+            .change_context(DownloadError::IoError)?;
+
+        serde_json::from_slice(&contents)
+            .into_report()
+            .change_context(DownloadError::ParseError)
+    }
+
+    #[must_use]
+    pub const fn layout(&self) -> AssetLayout {
+        if self.map_to_resources {
+            AssetLayout::Resources
+        } else if self.is_virtual {
+            AssetLayout::Virtual
+        } else {
+            AssetLayout::Objects
+        }
+    }
+
+    /// Gets the directory the game reads assets from, used for `${game_assets}`
+    #[must_use]
+    pub fn game_assets_directory(
+        &self,
+        index_id: &str,
+        assets_directory: &Path,
+        game_directory: &Path,
+    ) -> PathBuf {
+        self.layout()
+            .game_assets_directory(index_id, assets_directory, game_directory)
+    }
+
+    /// Copies the downloaded objects to `target` under their names, for the virtual and resources
+    /// layouts. Files that are already there with the right size and are newer than their object
+    /// are skipped, so nothing is hashed on launch
+    ///
+    /// # Errors
+    /// Errors if an object cannot be copied
+    pub async fn reconstruct(
+        &self,
+        assets_directory: &Path,
+        target: &Path,
+    ) -> Result<(), DownloadError> {
+        let objects = self
+            .objects
+            .iter()
+            .map(|(name, object)| (name.as_str(), object));
+
+        extract(objects, assets_directory, target).await?;
+
         Ok(())
     }
+
+    /// Gets an object by its name, e.g `minecraft/sounds/music/game/calm1.ogg`
+    #[must_use]
+    pub fn object(&self, name: &str) -> Option<&Object> {
+        self.objects.get(name)
+    }
+
+    /// Gets where the object with the given name is stored, see [`Object::path`]
+    #[must_use]
+    pub fn object_path(&self, name: &str, assets_directory: &Path) -> Option<PathBuf> {
+        self.object(name)
+            .map(|object| object.path(assets_directory))
+    }
+
+    /// Gets the objects whose names match the pattern, e.g `minecraft/sounds/music/**/*.ogg`.
+    /// `*` does not match `/`, `**` does
+    pub fn matching<'a>(
+        &'a self,
+        pattern: &'a Pattern,
+    ) -> impl Iterator<Item = (&'a str, &'a Object)> {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
+
+        self.objects
+            .iter()
+            .filter(move |(name, _)| pattern.matches_with(name, options))
+            .map(|(name, object)| (name.as_str(), object))
+    }
+
+    /// Copies the objects matching any of the patterns to `target` under their names, e.g
+    /// `minecraft/lang/*.json` ends up in `<target>/minecraft/lang`. Returns the extracted names
+    ///
+    /// # Errors
+    /// Errors if an object is not downloaded or cannot be copied
+    pub async fn extract(
+        &self,
+        patterns: &[Pattern],
+        assets_directory: &Path,
+        target: &Path,
+    ) -> Result<Vec<String>, DownloadError> {
+        let objects = patterns
+            .iter()
+            .flat_map(|pattern| self.matching(pattern))
+            .unique_by(|(name, _)| *name);
+
+        extract(objects, assets_directory, target).await
+    }
+
+    /// Lists the language codes the index has translations for, e.g `en_us`
+    #[must_use]
+    pub fn languages(&self) -> Vec<&str> {
+        self.objects
+            .keys()
+            .filter_map(|name| {
+                let file = name
+                    .strip_prefix("minecraft/lang/")
+                    .or_else(|| name.strip_prefix("lang/"))?;
+
+                file.strip_suffix(".json")
+                    .or_else(|| file.strip_suffix(".lang"))
+                    .filter(|code| !code.contains('/'))
+            })
+            .sorted_unstable()
+            .dedup()
+            .collect()
+    }
+
+    /// Lists the sound categories the index has sounds for, e.g `music` or `ambient`
+    #[must_use]
+    pub fn sound_categories(&self) -> Vec<&str> {
+        self.objects
+            .keys()
+            .filter_map(|name| {
+                let sound = name
+                    .strip_prefix("minecraft/sounds/")
+                    .or_else(|| name.strip_prefix("sounds/"))?;
+
+                sound.split_once('/').map(|(category, _)| category)
+            })
+            .sorted_unstable()
+            .dedup()
+            .collect()
+    }
+}
+
+/// Copies objects to `target` under their names, skipping files that are already there with the
+/// right size and are newer than their object
+async fn extract<'a>(
+    objects: impl Iterator<Item = (&'a str, &'a Object)>,
+    assets_directory: &Path,
+    target: &Path,
+) -> Result<Vec<String>, DownloadError> {
+    let mut extracted = vec![];
+
+    for (name, object) in objects {
+        let path = target.join(
+            enclosed_name(name)
+                .ok_or(DownloadError::UnsafePath)
+                .into_report()
+                .attach_printable_lazy(|| format!("Asset name {name}"))?,
+        );
+
+        let source = object.path(assets_directory);
+
+        if is_copied(&source, &path, object.size).await {
+            extracted.push(name.to_owned());
+            continue;
+        }
+
+        let parent_dir = path.parent().ok_or(DownloadError::IoError).into_report()?;
+
+        fs::create_dir_all(parent_dir)
+            .await
+            .into_report()
+            .change_context(DownloadError::IoError)?;
+
+        fs::copy(&source, &path)
+            .await
+            .into_report()
+            .change_context(DownloadError::IoError)
+            .attach_printable_lazy(|| format!("Could not copy asset {name}"))?;
+
+        extracted.push(name.to_owned());
+    }
+
+    Ok(extracted)
+}
+
+/// Whether `path` is a copy of `source`, judged by its size and modification time
+async fn is_copied(source: &Path, path: &Path, size: u64) -> bool {
+    let (Ok(object), Ok(copy)) = (fs::metadata(source).await, fs::metadata(path).await) else {
+        return false;
+    };
+
+    match (object.modified(), copy.modified()) {
+        (Ok(object_modified), Ok(modified)) => copy.len() == size && modified >= object_modified,
+        _ => false,
+    }
+}
+
+/// Gets an asset name as a relative path, or `None` if it is absolute or contains `..`, as it
+/// would be written outside of the target directory
+fn enclosed_name(name: &str) -> Option<&Path> {
+    let path = Path::new(name);
+    let is_enclosed = !name.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+
+    is_enclosed.then_some(path)
 }
 
 /// Getter methods
@@ -108,6 +392,16 @@ impl Assets {
     pub const fn objects(&self) -> &HashMap<String, Object> {
         &self.objects
     }
+
+    #[must_use]
+    pub const fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+
+    #[must_use]
+    pub const fn map_to_resources(&self) -> bool {
+        self.map_to_resources
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -124,72 +418,49 @@ impl Object {
     /// If you want to download to the assets directory with the appended path, use
     /// [`Assets::download_object`]
     ///
-    /// It will NOT redownload the file if it already exists
+    /// It will NOT redownload the file if it already exists with the right hash and size
     ///
     /// # Errors
-    /// Errors if the download fails or if the file cannot be created
+    /// Errors if the download fails, the file cannot be created, or the hash or size do not match
     pub async fn download(
         &self,
         path: PathBuf,
         client: &reqwest::Client,
         sender: &UnboundedSender<DownloadMessage<Self>>,
     ) -> Result<(), DownloadError> {
-        if path
-            .try_exists()
-            .into_report()
-            .change_context(DownloadError::IoError)?
-        {
-            return Ok(());
-        }
+        download_streamed(
+            client,
+            &self.url(),
+            &self.hash,
+            Some(self.size),
+            &path,
+            |downloaded| {
+                sender
+                    .send(DownloadMessage::DownloadProgress(self.clone(), downloaded))
+                    .into_report()
+                    .change_context(DownloadError::ChannelError)
+            },
+        )
+        .await
+    }
 
-        let url = format!(
+    /// Gets where the object is downloaded from
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!(
             "https://resources.download.minecraft.net/{}/{}",
             self.hash_start(),
             self.hash
-        );
-
-        let mut response = client
-            .get(&url)
-            .send()
-            .await
-            .into_report()
-            .change_context(DownloadError::ReqwestError)?
-            .bytes_stream();
-
-        let parent_dir = path.parent().ok_or(DownloadError::IoError).into_report()?;
-
-        tokio::fs::create_dir_all(parent_dir)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut file = tokio::fs::File::create(path)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut downloaded: u64 = 0;
-
-        while let Some(item) = response.next().await {
-            let item = item
-                .into_report()
-                .change_context(DownloadError::ReqwestError)?;
-
-            file.write_all(&item)
-                .await
-                .into_report()
-                .change_context(DownloadError::IoError)?;
-
-            let new = min(downloaded + (item.len() as u64), self.size());
-            downloaded = new;
-
-            sender
-                .send(DownloadMessage::DownloadProgress(self.clone(), new))
-                .into_report()
-                .change_context(DownloadError::ChannelError)?;
-        }
+        )
+    }
 
-        Ok(())
+    /// Gets where the object is stored, `assets/objects/<first 2 hash chars>/<hash>`
+    #[must_use]
+    pub fn path(&self, assets_directory: &Path) -> PathBuf {
+        assets_directory
+            .join("objects")
+            .join(self.hash_start())
+            .join(&self.hash)
     }
 }
 
@@ -210,3 +481,146 @@ impl Object {
         self.size
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::downloader::sha1_hex;
+
+    #[test]
+    fn layouts() {
+        let object = json!({ "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665 });
+
+        let modern: Assets = serde_json::from_value(json!({ "objects": {} })).unwrap();
+        let legacy: Assets = serde_json::from_value(json!({
+            "virtual": true,
+            "objects": { "sounds/ambient/cave/cave1.ogg": object }
+        }))
+        .unwrap();
+        let pre_1_6: Assets =
+            serde_json::from_value(json!({ "map_to_resources": true, "objects": {} })).unwrap();
+
+        let assets = Path::new("assets");
+        let game = Path::new(".minecraft");
+
+        assert_eq!(modern.game_assets_directory("5", assets, game), assets);
+        assert_eq!(
+            legacy.game_assets_directory("legacy", assets, game),
+            Path::new("assets/virtual/legacy")
+        );
+        assert_eq!(
+            pre_1_6.game_assets_directory("pre-1.6", assets, game),
+            Path::new(".minecraft/resources")
+        );
+        assert_eq!(
+            legacy.objects()["sounds/ambient/cave/cave1.ogg"].path(assets),
+            Path::new("assets/objects/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a")
+        );
+        assert_eq!(serde_json::to_value(&legacy).unwrap()["virtual"], true);
+    }
+
+    #[test]
+    fn names() {
+        let object = json!({ "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665 });
+        let assets: Assets = serde_json::from_value(json!({
+            "objects": {
+                "minecraft/lang/en_us.json": object,
+                "minecraft/lang/de_de.json": object,
+                "minecraft/sounds/music/game/calm1.ogg": object,
+                "minecraft/sounds/music/menu/menu1.ogg": object,
+                "minecraft/sounds/ambient/cave/cave1.ogg": object,
+                "minecraft/sounds.json": object,
+                "realms/lang/en_us.json": object
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(assets.languages(), ["de_de", "en_us"]);
+        assert_eq!(assets.sound_categories(), ["ambient", "music"]);
+        assert_eq!(
+            assets.object_path("minecraft/lang/en_us.json", Path::new("assets")),
+            Some(PathBuf::from(
+                "assets/objects/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a"
+            ))
+        );
+
+        let pattern = Pattern::new("minecraft/sounds/music/**/*.ogg").unwrap();
+        let names = assets
+            .matching(&pattern)
+            .map(|(name, _)| name)
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "minecraft/sounds/music/game/calm1.ogg",
+                "minecraft/sounds/music/menu/menu1.ogg"
+            ]
+        );
+
+        let pattern = Pattern::new("minecraft/*.json").unwrap();
+        assert_eq!(assets.matching(&pattern).count(), 1);
+    }
+
+    #[test_case("minecraft/lang/en_us.json", true)]
+    #[test_case("../../.bashrc", false)]
+    #[test_case("minecraft/../../escape.json", false)]
+    #[test_case("/etc/passwd", false)]
+    #[test_case("./minecraft/sounds.json", false)]
+    #[test_case("", false)]
+    fn enclosed_names(name: &str, enclosed: bool) {
+        assert_eq!(enclosed_name(name).is_some(), enclosed);
+    }
+
+    #[tokio::test]
+    async fn reconstruct_skips_copies() {
+        let hash = sha1_hex(b"abc");
+        let assets: Assets = serde_json::from_value(json!({
+            "virtual": true,
+            "objects": { "sounds/random/click.ogg": { "hash": hash, "size": 3 } }
+        }))
+        .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("copper-assets-{}", std::process::id()));
+        let assets_directory = dir.join("assets");
+        let target = dir.join("virtual");
+        let object = assets.objects()["sounds/random/click.ogg"].path(&assets_directory);
+        let copy = target.join("sounds/random/click.ogg");
+        fs::create_dir_all(object.parent().unwrap()).await.unwrap();
+        fs::write(&object, b"abc").await.unwrap();
+
+        assets
+            .reconstruct(&assets_directory, &target)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&copy).await.unwrap(), b"abc");
+
+        // only the size and modification time are checked, so the copy is not hashed
+        fs::write(&copy, b"xyz").await.unwrap();
+        assets
+            .reconstruct(&assets_directory, &target)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&copy).await.unwrap(), b"xyz");
+
+        let newer = SystemTime::now() + Duration::from_mins(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&object)
+            .unwrap()
+            .set_modified(newer)
+            .unwrap();
+        assets
+            .reconstruct(&assets_directory, &target)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&copy).await.unwrap(), b"abc");
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    cmp::min,
     error::Error,
     fmt::Display,
     path::{Path, PathBuf},
@@ -7,19 +6,30 @@ use std::{
 };
 
 use error_stack::{IntoReport, Result as ErrorStackResult, ResultExt};
-use futures::{stream, StreamExt, TryStreamExt};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use serde_json::to_string;
+use serde_json::Map;
 use tokio::{
     fs,
-    io::AsyncWriteExt,
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 use tracing::debug;
 
-use crate::downloader::{DownloadError, DownloadMessage, Downloader};
+use crate::{
+    atomic,
+    downloader::{
+        download_streamed, download_verified, remove_stale_temp_files, DownloadError,
+        DownloadMessage, DownloadSummary, Downloader, RetryPolicy,
+    },
+    natives::LibrarySelection,
+    platform::{OsName, Platform},
+    rules::{self, Decision, RuleContext},
+};
 
-use super::asset_index::Assets;
+use super::{
+    asset_index::Assets,
+    maven::{MavenCoordinate, MOJANG_LIBRARIES_URL},
+};
 
 #[derive(Debug)]
 pub enum SaveError {
@@ -38,36 +48,70 @@ impl Display for SaveError {
 
 impl Error for SaveError {}
 
+/// How a manifest is formatted when saved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A single line, like the manifests Mojang serves
+    #[default]
+    Compact,
+    /// Indented, for manifests people edit by hand
+    Pretty,
+}
+
 /// High level API
 impl Manifest {
     /// Saves the manifest to disk.
     ///
     /// # Errors
     /// Returns a [`SaveError`] if the manifest could not be serialized or if an IO error occurred.
-    #[tracing::instrument]
     pub async fn save_to_disk(&self, path: &Path) -> error_stack::Result<(), SaveError> {
-        debug!("Saving manifest to disk");
-        debug!("Serializing manifest to JSON");
-        let value = to_string(self)
-            .into_report()
-            .change_context(SaveError::SerializeError)?;
+        self.save_to_disk_with(path, OutputFormat::Compact).await
+    }
+
+    /// Saves the manifest to disk in the given format. The file is replaced atomically, and
+    /// unknown fields from the original file are kept.
+    ///
+    /// # Errors
+    /// Returns a [`SaveError`] if the manifest could not be serialized or if an IO error occurred.
+    pub async fn save_to_disk_with(
+        &self,
+        path: &Path,
+        format: OutputFormat,
+    ) -> error_stack::Result<(), SaveError> {
+        save_json(self, path, format).await
+    }
+}
 
-        let directory = path.parent().ok_or(SaveError::IOError).into_report()?;
+/// Serializes a manifest and atomically writes it to `path`
+#[tracing::instrument(skip(value))]
+pub(crate) async fn save_json<T: Serialize + Sync>(
+    value: &T,
+    path: &Path,
+    format: OutputFormat,
+) -> error_stack::Result<(), SaveError> {
+    debug!("Serializing manifest to JSON");
+    let value = match format {
+        OutputFormat::Compact => serde_json::to_string(value),
+        OutputFormat::Pretty => serde_json::to_string_pretty(value),
+    }
+    .into_report()
+    .change_context(SaveError::SerializeError)?;
 
-        if !directory.exists() {
-            debug!("Creating directory {}", directory.display());
-            fs::create_dir_all(directory)
-                .await
-                .into_report()
-                .change_context(SaveError::IOError)?;
-        }
+    let directory = path.parent().ok_or(SaveError::IOError).into_report()?;
 
-        debug!("Writing manifest to {}", path.display());
-        fs::write(path, value)
+    if !directory.exists() {
+        debug!("Creating directory {}", directory.display());
+        fs::create_dir_all(directory)
             .await
             .into_report()
-            .change_context(SaveError::IOError)
+            .change_context(SaveError::IOError)?;
     }
+
+    debug!("Writing manifest to {}", path.display());
+    atomic::write(path, value.as_bytes())
+        .await
+        .into_report()
+        .change_context(SaveError::IOError)
 }
 
 /// Getter methods
@@ -89,6 +133,11 @@ impl Manifest {
     pub const fn downloads(&self) -> &Downloads {
         &self.downloads
     }
+
+    #[must_use]
+    pub const fn logging(&self) -> Option<&Logging> {
+        self.logging.as_ref()
+    }
 }
 
 // Thank you quicktype, very cool :ferrisBased:
@@ -96,25 +145,34 @@ impl Manifest {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Manifest {
-    asset_index: AssetIndex,
-    assets: String,
-    downloads: Downloads,
-    id: String,
-    libraries: Vec<Library>,
-    main_class: String,
+    pub(crate) asset_index: AssetIndex,
+    pub(crate) assets: String,
+    pub(crate) downloads: Downloads,
+    pub(crate) id: String,
+    pub(crate) libraries: Vec<Library>,
+    pub(crate) main_class: String,
     /// Used before 1.13
-    minecraft_arguments: Option<String>,
-    arguments: Option<Arguments>,
-    minimum_launcher_version: i64,
-    java_version: Option<JavaVersion>,
-    release_time: String,
-    time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) minecraft_arguments: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) arguments: Option<Arguments>,
+    pub(crate) minimum_launcher_version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) java_version: Option<JavaVersion>,
+    pub(crate) release_time: String,
+    pub(crate) time: String,
     #[serde(rename = "type")]
-    manifest_type: Type,
-    logging: Option<Logging>,
-    compliance_level: Option<u8>,
+    pub(crate) manifest_type: Type,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) logging: Option<Logging>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) compliance_level: Option<u8>,
     /// What to merge from. During a merge, it will take the current manifest and merge this manifest into it
-    inherits_from: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) inherits_from: Option<PathBuf>,
+    /// Fields copper does not model (e.g loader specific keys), kept so saving is lossless
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, serde_json::Value>,
 }
 
 impl Manifest {
@@ -170,9 +228,22 @@ pub struct AssetIndex {
     size: i64,
     total_size: i64,
     url: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl AssetIndex {
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    #[must_use]
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
     /// Downloads the asset index
     ///
     /// # Errors
@@ -180,16 +251,52 @@ impl AssetIndex {
     pub async fn download(&self) -> Result<Assets, reqwest::Error> {
         reqwest::get(&self.url).await?.json().await
     }
+
+    /// Gets where the index is saved, `assets/indexes/<id>.json`
+    #[must_use]
+    pub fn path(&self, assets_dir: &Path) -> PathBuf {
+        assets_dir.join("indexes").join(format!("{}.json", self.id))
+    }
+
+    /// Downloads the asset index to `assets/indexes/<id>.json`, where the game looks for it, and
+    /// parses it
+    ///
+    /// # Errors
+    /// Errors if the index cannot be downloaded, does not match its sha1 or cannot be parsed
+    pub async fn save(
+        &self,
+        assets_dir: &Path,
+        client: &reqwest::Client,
+    ) -> ErrorStackResult<Assets, DownloadError> {
+        let path = self.path(assets_dir);
+
+        download_verified(
+            client,
+            &self.url,
+            &self.sha1,
+            u64::try_from(self.size).ok(),
+            &path,
+        )
+        .await?;
+
+        Assets::load(&path).await
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Downloads {
     client: DownloadClass,
     server: DownloadClass,
+    #[serde(skip_serializing_if = "Option::is_none")]
     client_mappings: Option<Mappings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     server_mappings: Option<Mappings>,
     /// Only present in version 1 of the manifest it seems
+    #[serde(skip_serializing_if = "Option::is_none")]
     windows_server: Option<DownloadClass>,
+    /// Fields copper does not model (e.g loader specific keys), kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Downloads {
@@ -224,6 +331,9 @@ pub struct DownloadClass {
     sha1: String,
     size: u64,
     url: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl DownloadClass {
@@ -236,6 +346,26 @@ impl DownloadClass {
     pub const fn size(&self) -> u64 {
         self.size
     }
+
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Downloads the file to `path`, verifying its sha1. Use [`ClassDownloader`] instead to get
+    /// progress updates.
+    ///
+    /// If the file already exists with the right sha1, it is not downloaded again.
+    ///
+    /// # Errors
+    /// Errors if the download fails, the file cannot be written, or the sha1 does not match
+    pub async fn download(
+        &self,
+        path: &Path,
+        client: &reqwest::Client,
+    ) -> ErrorStackResult<(), DownloadError> {
+        download_verified(client, &self.url, &self.sha1, Some(self.size), path).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -245,18 +375,28 @@ pub struct ClassDownloader {
     class: DownloadClass,
     path: PathBuf,
     sender: Option<UnboundedSender<DownloadMessage<DownloadClass>>>,
+
+    retry_policy: RetryPolicy,
 }
 
 impl ClassDownloader {
     #[must_use]
-    pub const fn new(client: reqwest::Client, class: DownloadClass, path: PathBuf) -> Self {
+    pub fn new(client: reqwest::Client, class: DownloadClass, path: PathBuf) -> Self {
         Self {
             client,
             class,
             path,
             sender: None,
+            retry_policy: RetryPolicy::default(),
         }
     }
+
+    /// Sets how a failed download is retried
+    #[must_use]
+    pub const fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 }
 
 impl Downloader for ClassDownloader {
@@ -269,62 +409,25 @@ impl Downloader for ClassDownloader {
     }
 
     async fn download(&self, item: Self::DownloadItem) -> ErrorStackResult<(), DownloadError> {
-        if self
-            .path
-            .try_exists()
-            .into_report()
-            .change_context(DownloadError::IoError)?
-        {
-            return Ok(());
-        }
-
-        let mut response = self
-            .client
-            .get(&item.url)
-            .send()
-            .await
-            .into_report()
-            .change_context(DownloadError::ReqwestError)?
-            .bytes_stream();
-
-        let parent_dir = self
-            .path
-            .parent()
-            .ok_or(DownloadError::IoError)
-            .into_report()?;
-
-        tokio::fs::create_dir_all(parent_dir)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut file = tokio::fs::File::create(&self.path)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut downloaded: u64 = 0;
-
-        while let Some(item) = response.next().await {
-            let item = item
-                .into_report()
-                .change_context(DownloadError::ReqwestError)?;
-
-            file.write_all(&item)
-                .await
-                .into_report()
-                .change_context(DownloadError::IoError)?;
-
-            let new = min(downloaded + (item.len() as u64), self.class.size);
-            downloaded = new;
-
-            self.sender
-                .as_ref()
-                .ok_or(DownloadError::ChannelError)?
-                .send(DownloadMessage::DownloadProgress(self.class.clone(), new))
-                .into_report()
-                .change_context(DownloadError::ChannelError)?;
-        }
+        let sender = self.sender.as_ref().ok_or(DownloadError::ChannelError)?;
+
+        self.retry_policy
+            .run(|| {
+                download_streamed(
+                    &self.client,
+                    &item.url,
+                    &item.sha1,
+                    Some(item.size),
+                    &self.path,
+                    |downloaded| {
+                        sender
+                            .send(DownloadMessage::DownloadProgress(item.clone(), downloaded))
+                            .into_report()
+                            .change_context(DownloadError::ChannelError)
+                    },
+                )
+            })
+            .await?;
 
         self.sender
             .as_ref()
@@ -334,7 +437,11 @@ impl Downloader for ClassDownloader {
             .change_context(DownloadError::ChannelError)
     }
 
-    async fn download_all(self: Arc<Self>) -> ErrorStackResult<(), DownloadError> {
+    /// The client cannot be skipped, so this always stops at the error
+    async fn download_all(
+        self: Arc<Self>,
+    ) -> ErrorStackResult<DownloadSummary<Self::DownloadItem>, DownloadError> {
+        remove_stale_temp_files([self.path.clone()]).await;
         self.download(self.class.clone()).await?;
 
         self.sender
@@ -344,7 +451,7 @@ impl Downloader for ClassDownloader {
             .into_report()
             .change_context(DownloadError::ChannelError)?;
 
-        Ok(())
+        Ok(DownloadSummary::default())
     }
 }
 
@@ -352,8 +459,13 @@ impl Downloader for ClassDownloader {
 pub struct Artifact {
     path: String,
     sha1: String,
-    size: u64,
+    /// Unknown for artifacts derived from a maven `url` without a `size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
     url: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Artifact {
@@ -368,7 +480,12 @@ impl Artifact {
     }
 
     #[must_use]
-    pub const fn size(&self) -> u64 {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> Option<u64> {
         self.size
     }
 
@@ -384,70 +501,61 @@ impl Artifact {
         client: &reqwest::Client,
         sender: &UnboundedSender<DownloadMessage<Self>>,
     ) -> ErrorStackResult<(), DownloadError> {
-        let path = library_path.join(self.path());
-
-        if path
-            .try_exists()
-            .into_report()
-            .change_context(DownloadError::IoError)?
-        {
+        // libraries generated by an installer (e.g Forge) have nowhere to be downloaded from
+        if self.url.is_empty() {
             return Ok(());
         }
 
-        let mut response = client
-            .get(&self.url)
-            .send()
-            .await
-            .into_report()
-            .change_context(DownloadError::ReqwestError)?
-            .bytes_stream();
-
-        let parent_dir = path.parent().ok_or(DownloadError::IoError).into_report()?;
-
-        tokio::fs::create_dir_all(parent_dir)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut file = tokio::fs::File::create(path)
-            .await
-            .into_report()
-            .change_context(DownloadError::IoError)?;
-
-        let mut downloaded: u64 = 0;
-
-        while let Some(item) = response.next().await {
-            let item = item
-                .into_report()
-                .change_context(DownloadError::ReqwestError)?;
-
-            file.write_all(&item)
-                .await
-                .into_report()
-                .change_context(DownloadError::IoError)?;
-
-            let new = min(downloaded + (item.len() as u64), self.size);
-            downloaded = new;
-
-            sender
-                .send(DownloadMessage::DownloadProgress(self.clone(), new))
-                .into_report()
-                .change_context(DownloadError::ChannelError)?;
-        }
+        let path = library_path.join(self.path());
 
-        Ok(())
+        download_streamed(
+            client,
+            &self.url,
+            &self.sha1,
+            self.size,
+            &path,
+            |downloaded| {
+                sender
+                    .send(DownloadMessage::DownloadProgress(self.clone(), downloaded))
+                    .into_report()
+                    .change_context(DownloadError::ChannelError)
+            },
+        )
+        .await
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Extract {
     exclude: Vec<String>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
+}
+
+impl Extract {
+    #[must_use]
+    pub fn exclude(&self) -> &[String] {
+        self.exclude.as_ref()
+    }
+
+    /// Whether a file in the natives jar should be skipped when extracting, e.g `META-INF/`
+    #[must_use]
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.exclude.iter().any(|prefix| name.starts_with(prefix))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Rule {
     action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
     os: Option<Os>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    features: Option<Features>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Rule {
@@ -461,45 +569,9 @@ impl Rule {
         self.os.as_ref()
     }
 
-    pub fn passes(&self) -> bool {
-        match self.action() {
-            Action::Allow => {
-                let Some(os) = self.os() else {
-                    return true;
-                };
-
-                let arch_rule = match os.arch().map(String::as_str) {
-                    Some("x86") => cfg!(target_arch = "x86"),
-                    Some(_) => todo!("Unknown arch"),
-                    None => true,
-                };
-
-                let os_rule = match os.name().map(String::as_str) {
-                    // windows users pls test
-                    #[cfg(target_os = "windows")]
-                    Some("windows") => {
-                        if let Some(ver) = &rule.os.version {
-                            if ver != "^10\\." {
-                                panic!("unrecognised windows version: {:?}, please report to https://github.com/glowsquid-launcher/copper/issues with the version you are using", ver);
-                            }
-
-                            IsWindows10OrGreater().unwrap_or(false)
-                        } else {
-                            true
-                        }
-                    }
-                    #[cfg(not(target_os = "windows"))]
-                    Some("windows") => false,
-                    Some("osx") => cfg!(target_os = "macos"),
-                    Some("linux") => cfg!(target_os = "linux"),
-                    Some(_) => todo!("Unknown os"),
-                    None => true,
-                };
-
-                arch_rule && os_rule
-            }
-            Action::Disallow => todo!("No disallow rules for jvm args"),
-        }
+    #[must_use]
+    pub const fn features(&self) -> Option<&Features> {
+        self.features.as_ref()
     }
 }
 
@@ -522,11 +594,17 @@ pub enum Name {
 pub struct JavaVersion {
     component: String,
     major_version: u8,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Logging {
     client: LoggingClient,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -536,14 +614,98 @@ pub struct LoggingClient {
     file: File,
     #[serde(rename = "type")]
     client_type: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
+}
+
+impl Logging {
+    #[must_use]
+    pub const fn client(&self) -> &LoggingClient {
+        &self.client
+    }
+}
+
+impl LoggingClient {
+    /// The JVM argument to pass, with `${path}` being the path to the config file
+    #[must_use]
+    pub fn argument(&self) -> &str {
+        &self.argument
+    }
+
+    #[must_use]
+    pub const fn file(&self) -> &File {
+        &self.file
+    }
+
+    #[must_use]
+    pub fn client_type(&self) -> &str {
+        &self.client_type
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct File {
     id: String,
     sha1: String,
-    size: i64,
+    /// Unknown for Mojang's patched log4j configs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
     url: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
+}
+
+impl File {
+    pub(crate) fn new(id: &str, sha1: &str, size: Option<u64>, url: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            sha1: sha1.to_owned(),
+            size,
+            url: url.to_owned(),
+            extra: Map::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    #[must_use]
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Gets the path the logging config is stored at, `<assets>/log_configs/<id>`
+    #[must_use]
+    pub fn path(&self, assets_directory: &Path) -> PathBuf {
+        assets_directory.join("log_configs").join(&self.id)
+    }
+
+    /// Downloads the logging config into the assets directory, returning its path.
+    ///
+    /// If the file already exists with the right sha1, it is not downloaded again.
+    ///
+    /// # Errors
+    /// Errors if the download fails, the file cannot be written, or the sha1 does not match
+    pub async fn download(
+        &self,
+        assets_directory: &Path,
+        client: &reqwest::Client,
+    ) -> ErrorStackResult<PathBuf, DownloadError> {
+        let path = self.path(assets_directory);
+
+        download_verified(client, &self.url, &self.sha1, self.size, &path).await?;
+
+        Ok(path)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -591,12 +753,21 @@ impl GameRule {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Features {
+    #[serde(skip_serializing_if = "Option::is_none")]
     is_demo_user: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     has_custom_resolution: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     has_quick_plays_support: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     is_quick_play_singleplayer: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     is_quick_play_multiplayer: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     is_quick_play_realms: Option<bool>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Features {
@@ -638,58 +809,95 @@ pub enum Value {
     StringArray(Vec<String>),
 }
 
+/// The natives artifacts of a library, keyed by classifier (e.g `natives-windows-64`)
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "kebab-case")]
-pub struct Classifiers {
-    natives_linux: Option<Artifact>,
-    natives_macos: Option<Artifact>,
-    natives_windows: Option<Artifact>,
-    natives_osx: Option<Artifact>,
-}
+#[serde(transparent)]
+pub struct Classifiers(IndexMap<String, Artifact>);
 
 impl Classifiers {
+    /// Every natives artifact, whichever OS it is for
+    pub fn all(&self) -> impl Iterator<Item = &Artifact> {
+        self.0.values()
+    }
+
+    /// Gets the artifact for a classifier
+    #[must_use]
+    pub fn get(&self, classifier: &str) -> Option<&Artifact> {
+        self.0.get(classifier)
+    }
+
     #[must_use]
-    pub const fn windows(&self) -> Option<&Artifact> {
-        self.natives_windows.as_ref()
+    pub fn windows(&self) -> Option<&Artifact> {
+        self.get("natives-windows")
     }
 
     #[must_use]
-    pub const fn linux(&self) -> Option<&Artifact> {
-        self.natives_linux.as_ref()
+    pub fn linux(&self) -> Option<&Artifact> {
+        self.get("natives-linux")
     }
 
     #[must_use]
     pub fn macos(&self) -> Option<&Artifact> {
-        self.natives_osx.as_ref().or(self.natives_macos.as_ref())
+        self.get("natives-osx")
+            .or_else(|| self.get("natives-macos"))
     }
 
     #[must_use]
     /// Returns the artifact for the current OS.
     pub fn current_os(&self) -> Option<&Artifact> {
-        if cfg!(target_os = "windows") {
-            self.windows()
-        } else if cfg!(target_os = "macos") {
-            self.macos()
-        } else if cfg!(target_os = "linux") {
-            self.linux()
-        } else {
-            None
+        self.for_platform(&Platform::current())
+    }
+
+    /// Returns the artifact for the given platform.
+    #[must_use]
+    pub fn for_platform(&self, platform: &Platform) -> Option<&Artifact> {
+        match platform.os() {
+            OsName::Windows => self.windows(),
+            OsName::Osx => self.macos(),
+            OsName::Linux => self.linux(),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Natives {
+    #[serde(skip_serializing_if = "Option::is_none")]
     linux: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     osx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     windows: Option<String>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
+}
+
+impl Natives {
+    /// Returns the natives classifier for the given platform, with `${arch}` replaced by the
+    /// pointer width (e.g `natives-windows-${arch}` becomes `natives-windows-64`).
+    #[must_use]
+    pub fn for_platform(&self, platform: &Platform) -> Option<String> {
+        let classifier = match platform.os() {
+            OsName::Windows => self.windows.as_ref(),
+            OsName::Osx => self.osx.as_ref(),
+            OsName::Linux => self.linux.as_ref(),
+        }?;
+
+        Some(classifier.replace("${arch}", platform.arch().bits()))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Os {
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     arch: Option<String>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Os {
@@ -712,8 +920,43 @@ impl Os {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Mappings {
     sha1: String,
-    size: i64,
+    size: u64,
     url: String,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
+}
+
+impl Mappings {
+    #[must_use]
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Downloads the Proguard mappings to `path`, verifying their sha1. They can then be parsed
+    /// with [`crate::mappings::ProguardMappings`].
+    ///
+    /// If the file already exists with the right sha1, it is not downloaded again.
+    ///
+    /// # Errors
+    /// Errors if the download fails, the file cannot be written, or the sha1 does not match
+    pub async fn download(
+        &self,
+        path: &Path,
+        client: &reqwest::Client,
+    ) -> ErrorStackResult<(), DownloadError> {
+        download_verified(client, &self.url, &self.sha1, Some(self.size), path).await
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -743,66 +986,209 @@ impl JvmClassRule {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Arguments {
-    #[serde(default)]
-    pub(crate) game: Vec<Game>,
-    #[serde(default)]
-    pub(crate) jvm: Vec<Jvm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) game: Option<Vec<Game>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) jvm: Option<Vec<Jvm>>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, serde_json::Value>,
 }
 
+/// The JVM arguments launchers pass for manifests that only have `minecraftArguments`
+pub const LEGACY_JVM_ARGUMENTS: [&str; 3] = [
+    "-Djava.library.path=${natives_directory}",
+    "-cp",
+    "${classpath}",
+];
+
 impl Arguments {
+    /// Converts a legacy `minecraftArguments` string into the modern format, using the default
+    /// [`LEGACY_JVM_ARGUMENTS`] as JVM arguments.
+    #[must_use]
+    pub fn from_legacy(minecraft_arguments: &str) -> Self {
+        Self {
+            game: Some(
+                minecraft_arguments
+                    .split_whitespace()
+                    .map(|arg| Game::String(arg.to_owned()))
+                    .collect(),
+            ),
+            jvm: Some(
+                LEGACY_JVM_ARGUMENTS
+                    .iter()
+                    .map(|arg| Jvm::String((*arg).to_owned()))
+                    .collect(),
+            ),
+            extra: Map::new(),
+        }
+    }
+
     #[must_use]
     pub fn game(&self) -> &[Game] {
-        self.game.as_ref()
+        self.game.as_deref().unwrap_or_default()
     }
 
     #[must_use]
     pub fn jvm(&self) -> &[Jvm] {
-        self.jvm.as_ref()
+        self.jvm.as_deref().unwrap_or_default()
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Library {
-    downloads: LibraryDownloads,
+    /// Not present for libraries that only have a maven `url` (Fabric, Quilt and legacy Forge)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    downloads: Option<LibraryDownloads>,
     name: String,
+    /// The maven repository the library is hosted in, used when there is no `downloads` block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// Only used alongside `url`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha1: Option<String>,
+    /// Only used alongside `url`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     rules: Option<Vec<Rule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     natives: Option<Natives>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     extract: Option<Extract>,
+    /// Fields copper does not model (e.g loader specific keys), kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl Library {
     #[must_use]
     pub fn check_rules_passes(&self) -> bool {
-        self.rules
-            .as_ref()
-            .map_or(true, |rules| rules.iter().all(Rule::passes))
+        self.rules_decision(&RuleContext::default()).is_allowed()
     }
 
+    /// Evaluates the rules of the library in the given context
     #[must_use]
-    pub fn parse_name(&self) -> Option<(&str, &str, &str)> {
-        let mut split = self.name.split(':');
-        let package = split.next()?;
-        let name = split.next()?;
-        let version = split.next()?;
+    pub fn rules_decision(&self, context: &RuleContext) -> Decision {
+        rules::evaluate(self.rules.iter().flatten(), context)
+    }
 
-        Some((package, name, version))
+    /// Parses the name of the library as a maven coordinate
+    #[must_use]
+    pub fn parse_name(&self) -> Option<MavenCoordinate> {
+        self.name.parse().ok()
     }
 
+    /// Gets the main artifact of the library.
+    ///
+    /// If the library has no `downloads` block, the artifact is derived from its name and maven
+    /// repository `url` (defaulting to Mojang's). Libraries that only carry natives have no main
+    /// artifact.
     #[must_use]
-    pub const fn downloads(&self) -> &LibraryDownloads {
-        &self.downloads
+    pub fn artifact(&self) -> Option<Artifact> {
+        if let Some(downloads) = &self.downloads {
+            return downloads.artifact().cloned();
+        }
+
+        if self.natives.is_some() {
+            return None;
+        }
+
+        self.parse_name()
+            .map(|coordinate| self.maven_artifact(&coordinate))
+    }
+
+    /// Gets the natives artifact of the library for the given platform, if it has any.
+    ///
+    /// The classifier comes from the `natives` map (e.g `natives-windows-${arch}`), falling back
+    /// to the usual `natives-<os>` classifiers when the library has none.
+    #[must_use]
+    pub fn native_artifact(&self, platform: &Platform) -> Option<Artifact> {
+        let classifier = self
+            .natives
+            .as_ref()
+            .and_then(|natives| natives.for_platform(platform));
+
+        if let Some(downloads) = &self.downloads {
+            let classifiers = downloads.classifiers()?;
+
+            return classifier
+                .map_or_else(
+                    || classifiers.for_platform(platform),
+                    |classifier| classifiers.get(&classifier),
+                )
+                .cloned();
+        }
+
+        let classifier = classifier?;
+        let coordinate = self.parse_name()?.with_classifier(classifier);
+
+        // the sha1 and size are for the main artifact, not the natives
+        Some(Artifact {
+            sha1: String::new(),
+            size: None,
+            ..self.maven_artifact(&coordinate)
+        })
+    }
+
+    fn maven_artifact(&self, coordinate: &MavenCoordinate) -> Artifact {
+        let repository = self.url.as_deref().unwrap_or(MOJANG_LIBRARIES_URL);
+
+        Artifact {
+            path: coordinate.path(),
+            sha1: self.sha1.clone().unwrap_or_default(),
+            size: self.size,
+            url: coordinate.url(repository),
+            extra: Map::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn downloads(&self) -> Option<&LibraryDownloads> {
+        self.downloads.as_ref()
     }
 
     #[must_use]
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    #[must_use]
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    #[must_use]
+    pub fn rules(&self) -> Option<&[Rule]> {
+        self.rules.as_deref()
+    }
+
+    /// The sha1 of a library that only has a maven `url`
+    #[must_use]
+    pub fn sha1(&self) -> Option<&str> {
+        self.sha1.as_deref()
+    }
+
+    #[must_use]
+    pub const fn natives(&self) -> Option<&Natives> {
+        self.natives.as_ref()
+    }
+
+    #[must_use]
+    pub const fn extract(&self) -> Option<&Extract> {
+        self.extract.as_ref()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LibraryDownloads {
+    #[serde(skip_serializing_if = "Option::is_none")]
     artifact: Option<Artifact>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     classifiers: Option<Classifiers>,
+    /// Fields copper does not model, kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
 impl LibraryDownloads {
@@ -822,10 +1208,14 @@ pub struct LibraryDownloader {
     libraries: Vec<Library>,
     libraries_directory: PathBuf,
 
+    platform: Platform,
+
     client: reqwest::Client,
     sender: Option<UnboundedSender<DownloadMessage<Artifact>>>,
 
     max_concurrent_downloads: usize,
+    retry_policy: RetryPolicy,
+    continue_on_error: bool,
 }
 
 impl LibraryDownloader {
@@ -840,10 +1230,34 @@ impl LibraryDownloader {
             libraries,
             client,
             libraries_directory,
+            platform: Platform::current(),
             sender: None,
             max_concurrent_downloads,
+            retry_policy: RetryPolicy::default(),
+            continue_on_error: false,
         }
     }
+
+    /// Downloads the libraries for the given platform instead of the host
+    #[must_use]
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Sets how failed downloads are retried
+    #[must_use]
+    pub const fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Keeps downloading the other libraries when one fails, see [`DownloadSummary`]
+    #[must_use]
+    pub const fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
 }
 
 impl Downloader for LibraryDownloader {
@@ -857,12 +1271,11 @@ impl Downloader for LibraryDownloader {
     }
 
     async fn download(&self, item: Self::DownloadItem) -> error_stack::Result<(), DownloadError> {
-        item.download(
-            self.libraries_directory.clone(),
-            &self.client,
-            self.sender.as_ref().ok_or(DownloadError::ChannelError)?,
-        )
-        .await?;
+        let sender = self.sender.as_ref().ok_or(DownloadError::ChannelError)?;
+
+        self.retry_policy
+            .run(|| item.download(self.libraries_directory.clone(), &self.client, sender))
+            .await?;
 
         self.sender
             .as_ref()
@@ -872,57 +1285,287 @@ impl Downloader for LibraryDownloader {
             .change_context(DownloadError::ChannelError)
     }
 
-    async fn download_all(self: Arc<Self>) -> error_stack::Result<(), DownloadError> {
+    async fn download_all(
+        self: Arc<Self>,
+    ) -> error_stack::Result<DownloadSummary<Self::DownloadItem>, DownloadError> {
         let new_self = self.clone();
-        let libraries = new_self
-            .libraries
-            .iter()
+        let libraries = LibrarySelection::new(&new_self.libraries, &self.platform)
+            .into_result()
+            .change_context(DownloadError::MissingNatives)?
+            .into_iter()
             .cloned()
-            .filter(Library::check_rules_passes);
-
-        let tasks = stream::iter(libraries)
-            .map(|object| {
-                let cloned_self = self.clone();
-                tokio::spawn(async move {
-                    if let Some(artifact) = object.downloads().artifact() {
-                        match cloned_self.download(artifact.clone()).await {
-                            Ok(it) => it,
-                            Err(err) => return Err(err),
-                        };
-                    };
-
-                    if let Some(native) = object
-                        .downloads()
-                        .classifiers()
-                        .and_then(|c| c.current_os())
-                    {
-                        match cloned_self.download(native.clone()).await {
-                            Ok(it) => it,
-                            Err(err) => return Err(err),
-                        };
-                    };
-
-                    Ok(())
+            .collect::<Vec<_>>();
+
+        remove_stale_temp_files(
+            libraries
+                .iter()
+                .flat_map(|library| {
+                    library
+                        .artifact()
+                        .into_iter()
+                        .chain(library.native_artifact(&self.platform))
                 })
-            })
-            .buffer_unordered(self.max_concurrent_downloads);
+                .map(|artifact| self.libraries_directory.join(artifact.path())),
+        )
+        .await;
 
-        tasks
-            .try_collect::<Vec<_>>()
-            .await
-            .into_report()
-            .change_context(DownloadError::JoinError)?
-            .into_iter()
-            .collect::<Result<_, _>>()?;
+        let tasks = libraries.into_iter().map(|object| {
+            let cloned_self = self.clone();
 
-        new_self
-            .sender
-            .clone()
-            .ok_or(DownloadError::ChannelError)?
+            async move {
+                let mut results = vec![];
+
+                let artifacts = object
+                    .artifact()
+                    .into_iter()
+                    .chain(object.native_artifact(&cloned_self.platform));
+
+                for artifact in artifacts {
+                    let result = cloned_self.download(artifact.clone()).await;
+                    let failed = result.is_err();
+
+                    results.push((artifact, result));
+
+                    if failed && !cloned_self.continue_on_error {
+                        break;
+                    }
+                }
+
+                results
+            }
+        });
+
+        let sender = new_self.sender.clone().ok_or(DownloadError::ChannelError)?;
+
+        let summary = DownloadSummary::collect_tasks(
+            tasks,
+            self.max_concurrent_downloads,
+            self.continue_on_error,
+            &sender,
+        )
+        .await?;
+
+        sender
             .send(DownloadMessage::DownloadedAll)
             .into_report()
             .change_context(DownloadError::ChannelError)?;
 
-        Ok(())
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        downloader::{is_downloaded, sha1_hex},
+        platform::Arch,
+    };
+
+    #[tokio::test]
+    async fn url_only_library() {
+        let library: Library = serde_json::from_value(json!({
+            "name": "net.fabricmc:tiny-mappings-parser:0.3.0+build.17",
+            "url": "https://maven.fabricmc.net/"
+        }))
+        .unwrap();
+
+        let artifact = library.artifact().unwrap();
+        assert_eq!(artifact.size(), None);
+        assert_eq!(
+            artifact.url(),
+            "https://maven.fabricmc.net/net/fabricmc/tiny-mappings-parser/0.3.0+build.17/tiny-mappings-parser-0.3.0+build.17.jar"
+        );
+
+        let dir = std::env::temp_dir().join(format!("copper-client-{}", std::process::id()));
+        let path = dir.join(artifact.path());
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(&path, b"jar").await.unwrap();
+
+        // neither the sha1 nor the size are known, so any existing file is accepted
+        assert!(is_downloaded(&path, artifact.sha1(), artifact.size()).await);
+
+        let library: Library = serde_json::from_value(json!({
+            "name": "net.fabricmc:tiny-mappings-parser:0.3.0+build.17",
+            "url": "https://maven.fabricmc.net/",
+            "sha1": sha1_hex(b"jar")
+        }))
+        .unwrap();
+        let artifact = library.artifact().unwrap();
+
+        assert!(is_downloaded(&path, artifact.sha1(), artifact.size()).await);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn maven_natives_have_no_size() {
+        let library: Library = serde_json::from_value(json!({
+            "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+            "natives": { "linux": "natives-linux" },
+            "size": 22
+        }))
+        .unwrap();
+
+        let platform = Platform::new(OsName::Linux, Arch::X86_64, None);
+        let native = library.native_artifact(&platform).unwrap();
+
+        assert_eq!(native.size(), None);
+        assert!(native.sha1().is_empty());
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let original = json!({
+            "assetIndex": {
+                "id": "5",
+                "sha1": "a",
+                "size": 1,
+                "totalSize": 2,
+                "url": "https://piston-meta.mojang.com/5.json"
+            },
+            "assets": "5",
+            "downloads": {
+                "client": { "sha1": "b", "size": 3, "url": "https://piston-data.mojang.com/client.jar" },
+                "server": { "sha1": "c", "size": 4, "url": "https://piston-data.mojang.com/server.jar" }
+            },
+            "id": "1.20.1-custom",
+            "libraries": [],
+            "mainClass": "net.minecraft.client.main.Main",
+            "arguments": {
+                "game": ["--username", "${auth_player_name}"],
+                "default-user-jvm": ["-Xmx2G"]
+            },
+            "minimumLauncherVersion": 21,
+            "releaseTime": "2023-06-12T13:25:51+00:00",
+            "time": "2023-06-12T13:25:51+00:00",
+            "type": "release",
+            "clientVersion": "1.20.1"
+        });
+
+        let manifest: Manifest = serde_json::from_value(original.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(&manifest).unwrap(), original);
+    }
+
+    /// `tv.twitch:twitch-platform` as shipped with 1.7.10 to 1.12, and LWJGL as shipped with 1.14
+    fn legacy_libraries() -> serde_json::Value {
+        json!([
+            {
+                "downloads": {
+                    "classifiers": {
+                        "natives-osx": {
+                            "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-osx.jar",
+                            "sha1": "5f9d1ee26257b3a33f0ca06fed335ef462af659f",
+                            "size": 455_359,
+                            "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-osx.jar"
+                        },
+                        "natives-windows-32": {
+                            "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-32.jar",
+                            "sha1": "206c4ccaecdbcfd2a1631150c69a97bbc9c20c11",
+                            "size": 474_225,
+                            "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-32.jar"
+                        },
+                        "natives-windows-64": {
+                            "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-64.jar",
+                            "sha1": "9fdd0fd5aed0817063dcf95b69349a171f447ebd",
+                            "size": 580_098,
+                            "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-64.jar"
+                        }
+                    }
+                },
+                "extract": { "exclude": ["META-INF/"] },
+                "name": "tv.twitch:twitch-platform:6.5",
+                "natives": {
+                    "linux": "natives-linux",
+                    "osx": "natives-osx",
+                    "windows": "natives-windows-${arch}"
+                },
+                "rules": [
+                    { "action": "allow" },
+                    { "action": "disallow", "os": { "name": "linux" } }
+                ]
+            },
+            {
+                "downloads": {
+                    "artifact": {
+                        "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2.jar",
+                        "sha1": "8ad6294407e15780b43e84929c40e4c5e997972e",
+                        "size": 321_900,
+                        "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2.jar"
+                    },
+                    "classifiers": {
+                        "javadoc": {
+                            "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-javadoc.jar",
+                            "sha1": "1f6b7050737559b775d797c0ea56612b8e373fd6",
+                            "size": 1_287_174,
+                            "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-javadoc.jar"
+                        },
+                        "natives-linux": {
+                            "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-linux.jar",
+                            "sha1": "ae7976827ca2a3741f6b9a843a89bacd637af350",
+                            "size": 124_776,
+                            "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-linux.jar"
+                        },
+                        "natives-macos": {
+                            "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-macos.jar",
+                            "sha1": "bbfb75693bdb714c0c69c2c9f9be73d259b43b62",
+                            "size": 48_462,
+                            "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-macos.jar"
+                        },
+                        "natives-windows": {
+                            "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-windows.jar",
+                            "sha1": "05359f3aa50d36352815fc662ea73e1c00d22170",
+                            "size": 279_593,
+                            "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-natives-windows.jar"
+                        },
+                        "sources": {
+                            "path": "org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-sources.jar",
+                            "sha1": "141b4c0e9d7209b26d1d9fbb9ef25bbdea3edb1b",
+                            "size": 255_418,
+                            "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/3.2.2/lwjgl-3.2.2-sources.jar"
+                        }
+                    }
+                },
+                "name": "org.lwjgl:lwjgl:3.2.2",
+                "natives": {
+                    "linux": "natives-linux",
+                    "osx": "natives-macos",
+                    "windows": "natives-windows"
+                },
+                "extract": { "exclude": ["META-INF/"] }
+            }
+        ])
+    }
+
+    #[test_case(OsName::Windows, Arch::X86_64, Some("natives-windows-64"))]
+    #[test_case(OsName::Windows, Arch::X86, Some("natives-windows-32"))]
+    #[test_case(OsName::Osx, Arch::X86_64, Some("natives-osx"))]
+    #[test_case(OsName::Linux, Arch::X86_64, None)]
+    fn legacy_native_artifact(os: OsName, arch: Arch, classifier: Option<&str>) {
+        let libraries: Vec<Library> = serde_json::from_value(legacy_libraries()).unwrap();
+
+        let native = libraries[0].native_artifact(&Platform::new(os, arch, None));
+
+        assert_eq!(
+            native.as_ref().map(Artifact::path),
+            classifier
+                .map(|classifier| format!(
+                    "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-{classifier}.jar"
+                ))
+                .as_deref()
+        );
+    }
+
+    #[test]
+    fn legacy_libraries_round_trip() {
+        let original = legacy_libraries();
+
+        let libraries: Vec<Library> = serde_json::from_value(original.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(&libraries).unwrap(), original);
     }
 }
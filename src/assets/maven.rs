@@ -0,0 +1,210 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// The repository libraries without a `url` are downloaded from
+pub const MOJANG_LIBRARIES_URL: &str = "https://libraries.minecraft.net/";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CoordinateError {
+    /// The coordinate does not have a group, artifact and version
+    MissingPart,
+    /// The coordinate has more than 4 `:` separated parts
+    TooManyParts,
+}
+
+impl Display for CoordinateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingPart => "Maven coordinate needs at least a group, artifact and version",
+            Self::TooManyParts => "Maven coordinate has too many parts",
+        })
+    }
+}
+
+impl Error for CoordinateError {}
+
+/// A maven coordinate in the form of `group:artifact:version[:classifier][@extension]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MavenCoordinate {
+    group: String,
+    artifact: String,
+    version: String,
+    classifier: Option<String>,
+    extension: String,
+}
+
+impl MavenCoordinate {
+    /// Returns the same coordinate with a different classifier
+    #[must_use]
+    pub fn with_classifier(&self, classifier: impl Into<String>) -> Self {
+        Self {
+            classifier: Some(classifier.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Gets the path of the file relative to the root of a maven repository, e.g
+    /// `org/ow2/asm/asm/9.5/asm-9.5.jar`
+    #[must_use]
+    pub fn path(&self) -> String {
+        let classifier = self
+            .classifier
+            .as_ref()
+            .map(|classifier| format!("-{classifier}"))
+            .unwrap_or_default();
+
+        format!(
+            "{}/{}/{}/{}-{}{}.{}",
+            self.group.replace('.', "/"),
+            self.artifact,
+            self.version,
+            self.artifact,
+            self.version,
+            classifier,
+            self.extension
+        )
+    }
+
+    /// Gets the URL of the file in the given maven repository
+    #[must_use]
+    pub fn url(&self, repository: &str) -> String {
+        format!("{}/{}", repository.trim_end_matches('/'), self.path())
+    }
+}
+
+/// Getter methods
+impl MavenCoordinate {
+    #[must_use]
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    #[must_use]
+    pub fn artifact(&self) -> &str {
+        &self.artifact
+    }
+
+    #[must_use]
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    #[must_use]
+    pub fn classifier(&self) -> Option<&str> {
+        self.classifier.as_deref()
+    }
+
+    #[must_use]
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+}
+
+impl FromStr for MavenCoordinate {
+    type Err = CoordinateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (coordinate, extension) = s.split_once('@').unwrap_or((s, "jar"));
+        let mut parts = coordinate.split(':').map(ToOwned::to_owned);
+
+        let mut next = || {
+            parts
+                .next()
+                .filter(|part| !part.is_empty())
+                .ok_or(CoordinateError::MissingPart)
+        };
+
+        let group = next()?;
+        let artifact = next()?;
+        let version = next()?;
+        let classifier = next().ok();
+
+        if next().is_ok() {
+            return Err(CoordinateError::TooManyParts);
+        }
+
+        Ok(Self {
+            group,
+            artifact,
+            version,
+            classifier,
+            extension: extension.to_owned(),
+        })
+    }
+}
+
+impl Display for MavenCoordinate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{classifier}")?;
+        }
+
+        if self.extension != "jar" {
+            write!(f, "@{}", self.extension)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("org.ow2.asm:asm:9.5", "org/ow2/asm/asm/9.5/asm-9.5.jar"; "plain")]
+    #[test_case(
+        "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar";
+        "classifier"
+    )]
+    #[test_case(
+        "de.oceanlabs.mcp:mcp_config:1.16.5-20210115.111550@zip",
+        "de/oceanlabs/mcp/mcp_config/1.16.5-20210115.111550/mcp_config-1.16.5-20210115.111550.zip";
+        "extension"
+    )]
+    #[test_case(
+        "net.minecraft:client:1.16.5:slim@jar",
+        "net/minecraft/client/1.16.5/client-1.16.5-slim.jar";
+        "classifier and extension"
+    )]
+    fn path(coordinate: &str, path: &str) {
+        let coordinate = coordinate.parse::<MavenCoordinate>().unwrap();
+
+        assert_eq!(coordinate.path(), path);
+    }
+
+    #[test_case("org.ow2.asm:asm:9.5")]
+    #[test_case("org.lwjgl:lwjgl:3.3.1:natives-linux")]
+    #[test_case("de.oceanlabs.mcp:mcp_config:1.16.5@zip")]
+    fn round_trip(coordinate: &str) {
+        assert_eq!(
+            coordinate.parse::<MavenCoordinate>().unwrap().to_string(),
+            coordinate
+        );
+    }
+
+    #[test_case("org.ow2.asm:asm", CoordinateError::MissingPart)]
+    #[test_case("org.ow2.asm::9.5", CoordinateError::MissingPart)]
+    #[test_case("a:b:c:d:e", CoordinateError::TooManyParts)]
+    fn invalid(coordinate: &str, error: CoordinateError) {
+        assert_eq!(coordinate.parse::<MavenCoordinate>(), Err(error));
+    }
+
+    #[test]
+    fn url() {
+        let coordinate = "net.fabricmc:intermediary:1.20.1"
+            .parse::<MavenCoordinate>()
+            .unwrap();
+
+        assert_eq!(
+            coordinate.url("https://maven.fabricmc.net/"),
+            "https://maven.fabricmc.net/net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 pub mod asset_index;
 pub mod client;
+pub mod maven;
 pub mod version;
 
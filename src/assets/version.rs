@@ -52,6 +52,12 @@ impl Manifest {
             .find(|v| v.id == self.latest.snapshot)
             .expect("Latest version to be in manifest")
     }
+
+    /// Returns the version with the given id, if it exists.
+    #[must_use]
+    pub fn version(&self, id: &str) -> Option<&Version> {
+        self.versions.iter().find(|v| v.id == id)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -0,0 +1,148 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::{fs, io::AsyncWriteExt};
+
+/// Gets a unique temporary path next to `path`, e.g `.1.20.1.json.1234.0.tmp`. Being in the same
+/// directory keeps the final rename on the same filesystem
+pub fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// How long a temporary file has to be left untouched before it is considered abandoned. Files
+/// being downloaded are written to continuously, so another process sharing the directory never
+/// has its in-progress files removed
+pub const STALE_AFTER: Duration = Duration::from_hours(1);
+
+/// Removes temporary files in `directory` left by other processes, e.g an interrupted download.
+///
+/// Only files untouched for [`STALE_AFTER`] are removed. This reads the whole directory, so call
+/// it once per directory rather than once per file
+pub async fn remove_stale(directory: &Path) {
+    let Ok(mut entries) = fs::read_dir(directory).await else {
+        return;
+    };
+
+    let own = format!(".{}.", std::process::id());
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        if !is_temp_file(&file_name) || file_name.contains(&own) {
+            continue;
+        }
+
+        let is_stale = entry
+            .metadata()
+            .await
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| {
+                modified
+                    .elapsed()
+                    .is_ok_and(|elapsed| elapsed >= STALE_AFTER)
+            });
+
+        if is_stale {
+            // another process may have removed it already
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+/// Whether the file name was made by [`temp_path`], i.e `.<name>.<pid>.<counter>.tmp`
+fn is_temp_file(file_name: &str) -> bool {
+    let Some(rest) = file_name
+        .strip_prefix('.')
+        .and_then(|rest| rest.strip_suffix(".tmp"))
+    else {
+        return false;
+    };
+
+    let mut parts = rest.rsplitn(3, '.');
+    let is_number = |part: Option<&str>| {
+        part.is_some_and(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+    };
+
+    is_number(parts.next()) && is_number(parts.next()) && parts.next().is_some()
+}
+
+/// Writes a file atomically. The contents are written and synced to a temporary file, which is
+/// then renamed over `path`, so a crash never leaves a half written file behind
+pub async fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+
+    let result = async {
+        let mut file = fs::File::create(&temp).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        fs::rename(&temp, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        // the temporary file may not exist, in which case there is nothing to clean up
+        let _ = fs::remove_file(&temp).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stale_temp_files() {
+        let dir = std::env::temp_dir().join(format!("copper-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+
+        let path = dir.join("client.jar");
+        let stale = dir.join(".client.jar.0.0.tmp");
+        let in_progress = dir.join(".client.jar.1.0.tmp");
+        let own = temp_path(&path);
+        let unrelated = dir.join(".client.jar.tmp");
+
+        for file in [&stale, &in_progress, &own, &unrelated] {
+            fs::write(file, b"partial").await.unwrap();
+        }
+
+        std::fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_AFTER)
+            .unwrap();
+
+        remove_stale(&dir).await;
+
+        assert!(!stale.exists());
+        assert!(in_progress.exists());
+        assert!(own.exists());
+        assert!(unrelated.exists());
+
+        write(&path, b"complete").await.unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"complete");
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter, Write},
+    iter,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde_json::json;
+use tokio::process::{Child, Command};
+
+use crate::{atomic, parser::Argument};
+
+#[derive(Debug)]
+pub enum CommandError {
+    IoError,
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::IoError => "Failed during IO task",
+        })
+    }
+}
+
+impl Error for CommandError {}
+
+/// The full command used to launch the game, see [`crate::launcher::Launcher::command`].
+///
+/// Rendering it (as a shell script or JSON) masks sensitive arguments like the access token,
+/// unless [`LaunchCommand::reveal_secrets`] is used.
+#[derive(Debug, Clone)]
+pub struct LaunchCommand {
+    java_path: PathBuf,
+    jvm_arguments: Vec<Argument>,
+    main_class: String,
+    game_arguments: Vec<Argument>,
+    working_directory: Option<PathBuf>,
+    environment: BTreeMap<String, String>,
+    reveal_secrets: bool,
+}
+
+impl LaunchCommand {
+    #[must_use]
+    pub const fn new(
+        java_path: PathBuf,
+        jvm_arguments: Vec<Argument>,
+        main_class: String,
+        game_arguments: Vec<Argument>,
+    ) -> Self {
+        Self {
+            java_path,
+            jvm_arguments,
+            main_class,
+            game_arguments,
+            working_directory: None,
+            environment: BTreeMap::new(),
+            reveal_secrets: false,
+        }
+    }
+
+    /// Runs the command in the given directory instead of the launcher's own working directory
+    #[must_use]
+    pub fn with_working_directory(mut self, working_directory: PathBuf) -> Self {
+        self.working_directory = Some(working_directory);
+        self
+    }
+
+    /// Sets environment variables for the game, on top of the launcher's own environment
+    #[must_use]
+    pub fn with_environment(mut self, environment: BTreeMap<String, String>) -> Self {
+        self.environment.extend(environment);
+        self
+    }
+
+    /// Renders sensitive arguments as is. Only use this if the output will not be shared
+    #[must_use]
+    pub const fn reveal_secrets(mut self) -> Self {
+        self.reveal_secrets = true;
+        self
+    }
+
+    /// Gets every argument passed to java: the JVM arguments, main class and game arguments
+    pub fn arguments(&self) -> impl Iterator<Item = &str> {
+        self.jvm_arguments
+            .iter()
+            .map(Argument::value)
+            .chain(iter::once(self.main_class.as_str()))
+            .chain(self.game_arguments.iter().map(Argument::value))
+    }
+
+    /// Gets the arguments as they are rendered, masked unless secrets are revealed
+    fn rendered_arguments(&self) -> impl Iterator<Item = &str> {
+        self.jvm_arguments
+            .iter()
+            .map(|argument| self.render(argument))
+            .chain(iter::once(self.main_class.as_str()))
+            .chain(
+                self.game_arguments
+                    .iter()
+                    .map(|argument| self.render(argument)),
+            )
+    }
+
+    /// Spawns the command with piped stdout and stderr
+    ///
+    /// # Errors
+    /// Errors if the process cannot be spawned
+    pub fn spawn(&self) -> Result<Child, CommandError> {
+        let mut command = Command::new(&self.java_path);
+
+        if let Some(working_directory) = &self.working_directory {
+            command.current_dir(working_directory);
+        }
+
+        command
+            .envs(&self.environment)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .args(self.arguments())
+            .spawn()
+            .into_report()
+            .change_context(CommandError::IoError)
+    }
+
+    /// Renders the command as a POSIX shell script
+    #[must_use]
+    pub fn to_shell_script(&self) -> String {
+        let mut script = String::from("#!/bin/sh\n");
+
+        if let Some(working_directory) = &self.working_directory {
+            let _ = writeln!(
+                script,
+                "cd {} || exit 1",
+                shell_quote(&working_directory.to_string_lossy())
+            );
+        }
+
+        for (key, value) in &self.environment {
+            let _ = writeln!(script, "export {key}={}", shell_quote(value));
+        }
+
+        let _ = write!(
+            script,
+            "exec {}",
+            shell_quote(&self.java_path.to_string_lossy())
+        );
+
+        for argument in self.rendered_arguments() {
+            let _ = write!(script, " \\\n  {}", shell_quote(argument));
+        }
+
+        script.push('\n');
+        script
+    }
+
+    /// Renders the command as pretty printed JSON
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let render = |arguments: &[Argument]| {
+            arguments
+                .iter()
+                .map(|argument| self.render(argument).to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let value = json!({
+            "javaPath": self.java_path,
+            "jvmArguments": render(&self.jvm_arguments),
+            "mainClass": self.main_class,
+            "gameArguments": render(&self.game_arguments),
+            "workingDirectory": self.working_directory,
+            "environment": self.environment,
+        });
+
+        format!("{value:#}")
+    }
+
+    /// Saves the command as an executable shell script
+    ///
+    /// # Errors
+    /// Errors if the script cannot be written
+    pub async fn save_shell_script(&self, path: &Path) -> Result<(), CommandError> {
+        atomic::write(path, self.to_shell_script().as_bytes())
+            .await
+            .into_report()
+            .change_context(CommandError::IoError)?;
+
+        #[cfg(unix)]
+        {
+            use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+            tokio::fs::set_permissions(path, Permissions::from_mode(0o755))
+                .await
+                .into_report()
+                .change_context(CommandError::IoError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Saves the command as JSON
+    ///
+    /// # Errors
+    /// Errors if the file cannot be written
+    pub async fn save_json(&self, path: &Path) -> Result<(), CommandError> {
+        atomic::write(path, self.to_json().as_bytes())
+            .await
+            .into_report()
+            .change_context(CommandError::IoError)
+    }
+
+    fn render<'a>(&self, argument: &'a Argument) -> &'a str {
+        if self.reveal_secrets {
+            argument.value()
+        } else {
+            argument.redacted()
+        }
+    }
+}
+
+/// Getter methods
+impl LaunchCommand {
+    #[must_use]
+    pub fn java_path(&self) -> &Path {
+        &self.java_path
+    }
+
+    #[must_use]
+    pub fn jvm_arguments(&self) -> &[Argument] {
+        &self.jvm_arguments
+    }
+
+    #[must_use]
+    pub fn main_class(&self) -> &str {
+        &self.main_class
+    }
+
+    #[must_use]
+    pub fn game_arguments(&self) -> &[Argument] {
+        &self.game_arguments
+    }
+
+    /// The directory the command runs in, or `None` to run in the launcher's working directory
+    #[must_use]
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_deref()
+    }
+
+    #[must_use]
+    pub const fn environment(&self) -> &BTreeMap<String, String> {
+        &self.environment
+    }
+}
+
+/// Quotes an argument for a POSIX shell, leaving simple ones as is
+fn shell_quote(argument: &str) -> String {
+    let is_simple = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c));
+
+    if is_simple {
+        argument.to_owned()
+    } else {
+        format!("'{}'", argument.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Placeholders, REDACTED};
+
+    fn command() -> LaunchCommand {
+        let mut placeholders = Placeholders::new();
+        placeholders.insert("auth_player_name", "Steve's alt");
+        placeholders.insert_sensitive("auth_access_token", "secret-token");
+
+        let arguments = |arguments: &[&str]| {
+            arguments
+                .iter()
+                .map(|argument| placeholders.resolve(argument))
+                .collect()
+        };
+
+        LaunchCommand::new(
+            PathBuf::from("/usr/bin/java"),
+            arguments(&["-Xmx2G", "-cp", "a.jar:b.jar"]),
+            "net.minecraft.client.main.Main".to_owned(),
+            arguments(&[
+                "--username",
+                "${auth_player_name}",
+                "--accessToken",
+                "${auth_access_token}",
+            ]),
+        )
+        .with_working_directory(PathBuf::from("/home/steve/.minecraft"))
+        .with_environment(BTreeMap::from([(
+            "MESA_GL_VERSION_OVERRIDE".to_owned(),
+            "3.3".to_owned(),
+        )]))
+    }
+
+    #[test]
+    fn shell_script() {
+        assert_eq!(
+            command().to_shell_script(),
+            r"#!/bin/sh
+cd /home/steve/.minecraft || exit 1
+export MESA_GL_VERSION_OVERRIDE=3.3
+exec /usr/bin/java \
+  -Xmx2G \
+  -cp \
+  a.jar:b.jar \
+  net.minecraft.client.main.Main \
+  --username \
+  'Steve'\''s alt' \
+  --accessToken \
+  '<redacted>'
+"
+        );
+        assert!(command()
+            .reveal_secrets()
+            .to_shell_script()
+            .contains("secret-token"));
+    }
+
+    #[test]
+    fn debug() {
+        let debug = format!("{:?}", command());
+
+        assert!(!debug.contains("secret-token"));
+        assert!(debug.contains(REDACTED));
+    }
+
+    #[test]
+    fn json() {
+        let value: serde_json::Value = serde_json::from_str(&command().to_json()).unwrap();
+
+        assert_eq!(value["mainClass"], "net.minecraft.client.main.Main");
+        assert_eq!(value["gameArguments"][3], REDACTED);
+        assert_eq!(value["environment"]["MESA_GL_VERSION_OVERRIDE"], "3.3");
+    }
+}
@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+
+use itertools::Itertools;
+use regex::{Captures, Regex};
+
+use crate::mappings::{ClassMapping, ProguardMappings};
+
+/// A stack frame, e.g `at fkx.a(SourceFile:123)`. Module prefixes like `java.base/` or
+/// `TRANSFORMER/minecraft@1.20.1/` are kept as is
+const FRAME_PATTERN: &str = r"(?m)^(?P<prefix>\s*at\s+(?:[^\s(/]*/)*)(?P<class>[\w$.]+)\.(?P<method>[\w$<>]+)\((?P<source>[^)\n]*)\)";
+
+/// The line naming an exception, e.g `Caused by: fkx: message`
+const EXCEPTION_PATTERN: &str =
+    r"(?m)^(?P<prefix>\s*(?:Caused by: |Suppressed: )?)(?P<class>[\w$.]+)(?P<suffix>:\s|:?$)";
+
+/// Rewrites obfuscated names in crash reports and stack traces using a version's mappings.
+///
+/// Only stack frames and exception lines are rewritten, as short obfuscated names like `a` would
+/// otherwise clash with the rest of the report.
+#[derive(Debug)]
+pub struct Deobfuscator<'a> {
+    mappings: &'a ProguardMappings,
+    frame: Regex,
+    exception: Regex,
+}
+
+impl<'a> Deobfuscator<'a> {
+    /// Creates a deobfuscator for the mappings, usually the version's `client_mappings`
+    ///
+    /// # Panics
+    /// Never, the patterns are known to be valid
+    #[must_use]
+    pub fn new(mappings: &'a ProguardMappings) -> Self {
+        Self {
+            mappings,
+            frame: Regex::new(FRAME_PATTERN).expect("frame pattern is valid"),
+            exception: Regex::new(EXCEPTION_PATTERN).expect("exception pattern is valid"),
+        }
+    }
+
+    /// Deobfuscates a crash report or raw stack trace
+    #[must_use]
+    pub fn deobfuscate(&self, text: &str) -> String {
+        let text = self.frame.replace_all(text, |captures: &Captures| {
+            let class = &captures["class"];
+            let line = captures["source"]
+                .rsplit_once(':')
+                .and_then(|(_, line)| line.parse().ok());
+
+            let method = self
+                .mappings
+                .class_by_obfuscated(class)
+                .and_then(|mapping| method(mapping, &captures["method"], line))
+                .map_or_else(|| Cow::Borrowed(&captures["method"]), Cow::Owned);
+
+            format!(
+                "{}{}.{}({})",
+                &captures["prefix"],
+                self.class_or_obfuscated(class),
+                method,
+                &captures["source"]
+            )
+        });
+
+        self.exception
+            .replace_all(&text, |captures: &Captures| {
+                format!(
+                    "{}{}{}",
+                    &captures["prefix"],
+                    self.class_or_obfuscated(&captures["class"]),
+                    &captures["suffix"]
+                )
+            })
+            .into_owned()
+    }
+
+    /// Deobfuscates a class name. Inner classes missing from the mappings (e.g anonymous
+    /// classes like `fkx$1`) keep their suffix on the mapped outer class
+    #[must_use]
+    pub fn class(&self, obfuscated: &str) -> Option<String> {
+        if let Some(class) = self.mappings.class_by_obfuscated(obfuscated) {
+            return Some(class.named().to_owned());
+        }
+
+        let (outer, inner) = obfuscated.rsplit_once('$')?;
+
+        self.class(outer).map(|outer| format!("{outer}${inner}"))
+    }
+
+    fn class_or_obfuscated<'b>(&self, obfuscated: &'b str) -> Cow<'b, str> {
+        self.class(obfuscated)
+            .map_or(Cow::Borrowed(obfuscated), Cow::Owned)
+    }
+}
+
+/// Deobfuscates a method name. Overloads can share an obfuscated name, so the line number is
+/// used to pick the right one. If that is not enough, all candidates are listed as `a|b`
+fn method(class: &ClassMapping, obfuscated: &str, line: Option<u32>) -> Option<String> {
+    let candidates = class.methods_by_obfuscated(obfuscated).collect::<Vec<_>>();
+
+    let at_line = line.and_then(|line| {
+        candidates
+            .iter()
+            .find(|method| method.lines().is_some_and(|lines| lines.contains(line)))
+    });
+
+    if let Some(method) = at_line {
+        return Some(method.named().to_owned());
+    }
+
+    let names = candidates
+        .iter()
+        .map(|method| method.named())
+        .unique()
+        .join("|");
+
+    (!names.is_empty()).then_some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: &str = "\
+net.minecraft.client.Minecraft -> enn:
+    10:20:void tick() -> a
+    21:30:void render(boolean) -> a
+    31:31:void stop() -> b
+net.minecraft.client.Minecraft$ChatStatus -> enn$a:
+    5:8:boolean isChatAllowed(boolean) -> a
+net.minecraft.ReportedException -> x:
+";
+
+    #[test]
+    fn stack_trace() {
+        let mappings = ProguardMappings::parse(MAPPINGS).unwrap();
+        let deobfuscator = Deobfuscator::new(&mappings);
+
+        let trace = "\
+x: Unexpected error
+\tat enn.a(SourceFile:25)
+\tat enn$a.a(SourceFile:6)
+\tat enn$1.run(SourceFile:3)
+\tat java.base/java.lang.Thread.run(Thread.java:833)
+Caused by: java.lang.NullPointerException: Cannot invoke \"enn.b()\"
+\tat enn.a(SourceFile:15)";
+
+        assert_eq!(
+            deobfuscator.deobfuscate(trace),
+            "\
+net.minecraft.ReportedException: Unexpected error
+\tat net.minecraft.client.Minecraft.render(SourceFile:25)
+\tat net.minecraft.client.Minecraft$ChatStatus.isChatAllowed(SourceFile:6)
+\tat net.minecraft.client.Minecraft$1.run(SourceFile:3)
+\tat java.base/java.lang.Thread.run(Thread.java:833)
+Caused by: java.lang.NullPointerException: Cannot invoke \"enn.b()\"
+\tat net.minecraft.client.Minecraft.tick(SourceFile:15)"
+        );
+    }
+
+    #[test]
+    fn ambiguous_method() {
+        let mappings = ProguardMappings::parse(MAPPINGS).unwrap();
+        let deobfuscator = Deobfuscator::new(&mappings);
+
+        assert_eq!(
+            deobfuscator.deobfuscate("\tat enn.a(Unknown Source)"),
+            "\tat net.minecraft.client.Minecraft.tick|render(Unknown Source)"
+        );
+    }
+}
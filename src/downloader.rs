@@ -1,11 +1,29 @@
-use error_stack::Result;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use futures::{Stream, StreamExt};
+use itertools::Itertools;
+use sha1::{Digest, Sha1};
 use std::{
+    cmp::min,
     error::Error,
     fmt::{self, Display, Formatter},
+    future::Future,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        Semaphore,
+    },
+    task::JoinSet,
+};
+use tracing::{debug, warn};
+
+use crate::atomic;
 
 #[derive(Debug)]
 pub enum DownloadError {
@@ -13,21 +31,498 @@ pub enum DownloadError {
     IoError,
     JoinError,
     ChannelError,
+    /// The manifest has no natives for the platform being downloaded for
+    MissingNatives,
+    /// The downloaded file does not match its sha1
+    IntegrityError {
+        expected: String,
+        actual: String,
+    },
+    /// The downloaded file does not have the expected size
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// A downloaded file (e.g an asset index) could not be parsed
+    ParseError,
+    /// The server answered with an error status, e.g 404
+    StatusError {
+        status: u16,
+    },
+    /// A name from an index (e.g an asset name) would be written outside of its directory
+    UnsafePath,
+}
+
+impl DownloadError {
+    /// Whether the error may go away by downloading again, e.g a timeout or a corrupt transfer
+    #[must_use]
+    pub const fn is_retryable(&self) -> bool {
+        match self {
+            Self::ReqwestError | Self::IntegrityError { .. } | Self::SizeMismatch { .. } => true,
+            Self::StatusError { status } => matches!(status, 408 | 429 | 500..=599),
+            Self::IoError
+            | Self::JoinError
+            | Self::ChannelError
+            | Self::MissingNatives
+            | Self::ParseError
+            | Self::UnsafePath => false,
+        }
+    }
 }
 
 impl Display for DownloadError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(match self {
-            Self::ReqwestError => "reqwest error",
-            Self::IoError => "io error",
-            Self::JoinError => "join error",
-            Self::ChannelError => "channel error",
-        })
+        match self {
+            Self::ReqwestError => f.write_str("reqwest error"),
+            Self::IoError => f.write_str("io error"),
+            Self::JoinError => f.write_str("join error"),
+            Self::ChannelError => f.write_str("channel error"),
+            Self::MissingNatives => f.write_str("missing natives for platform"),
+            Self::IntegrityError { expected, actual } => {
+                write!(f, "sha1 mismatch, expected {expected} but got {actual}")
+            }
+            Self::SizeMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "size mismatch, expected {expected} bytes but got {actual}"
+                )
+            }
+            Self::ParseError => f.write_str("parse error"),
+            Self::StatusError { status } => write!(f, "unexpected HTTP status {status}"),
+            Self::UnsafePath => f.write_str("path escapes its directory"),
+        }
     }
 }
 
 impl Error for DownloadError {}
 
+/// Hex encoded SHA-1 of the given bytes, the format manifests use
+#[must_use]
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    finalize_hex(Sha1::new_with_prefix(bytes))
+}
+
+fn finalize_hex(hasher: Sha1) -> String {
+    format!("{:x}", hasher.finalize())
+}
+
+/// Sends a GET request, treating error statuses (e.g a 404 page) as errors instead of content
+async fn get(client: &reqwest::Client, url: &str) -> Result<reqwest::Response, DownloadError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .into_report()
+        .change_context(DownloadError::ReqwestError)
+        .attach_printable_lazy(|| format!("Downloading {url}"))?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        return Err(Report::new(DownloadError::StatusError {
+            status: status.as_u16(),
+        })
+        .attach_printable(format!("Downloading {url}")));
+    }
+
+    Ok(response)
+}
+
+/// Checks a file against its expected sha1 and size. An empty sha1 (e.g libraries generated by an
+/// installer) or unknown size is not checked
+fn check_integrity(
+    sha1: &str,
+    size: Option<u64>,
+    actual_sha1: &str,
+    actual_size: u64,
+) -> Result<(), DownloadError> {
+    if let Some(size) = size.filter(|size| *size != actual_size) {
+        return Err(Report::new(DownloadError::SizeMismatch {
+            expected: size,
+            actual: actual_size,
+        }));
+    }
+
+    if !sha1.is_empty() && !sha1.eq_ignore_ascii_case(actual_sha1) {
+        return Err(Report::new(DownloadError::IntegrityError {
+            expected: sha1.to_owned(),
+            actual: actual_sha1.to_owned(),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Whether the file at `path` exists and matches its sha1 and size, so it does not need to be
+/// downloaded again
+pub(crate) async fn is_downloaded(path: &Path, sha1: &str, size: Option<u64>) -> bool {
+    let Ok(existing) = fs::read(path).await else {
+        return false;
+    };
+
+    let result = check_integrity(sha1, size, &sha1_hex(&existing), existing.len() as u64);
+
+    if let Err(e) = &result {
+        debug!("{} is corrupt, downloading again: {e}", path.display());
+    }
+
+    result.is_ok()
+}
+
+/// Downloads a file into memory and writes it to `path`, verifying its sha1 and size first.
+///
+/// Nothing is downloaded if the file already exists with the right sha1 and size.
+///
+/// # Errors
+/// Errors if the download fails, the file cannot be written, or the sha1 or size do not match
+pub(crate) async fn download_verified(
+    client: &reqwest::Client,
+    url: &str,
+    sha1: &str,
+    size: Option<u64>,
+    path: &Path,
+) -> Result<(), DownloadError> {
+    if is_downloaded(path, sha1, size).await {
+        return Ok(());
+    }
+
+    let bytes = get(client, url)
+        .await?
+        .bytes()
+        .await
+        .into_report()
+        .change_context(DownloadError::ReqwestError)?;
+
+    check_integrity(sha1, size, &sha1_hex(&bytes), bytes.len() as u64)
+        .attach_printable_lazy(|| format!("Downloaded from {url}"))?;
+
+    let parent_dir = path.parent().ok_or(DownloadError::IoError).into_report()?;
+
+    fs::create_dir_all(parent_dir)
+        .await
+        .into_report()
+        .change_context(DownloadError::IoError)?;
+
+    // single files are downloaded on their own, so this runs once per download
+    atomic::remove_stale(parent_dir).await;
+
+    atomic::write(path, &bytes)
+        .await
+        .into_report()
+        .change_context(DownloadError::IoError)
+}
+
+/// Removes abandoned temporary files next to the given files, reading every directory once.
+/// Downloaders call this once before [`download_streamed`]ing their files
+pub(crate) fn remove_stale_temp_files(
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> impl Future<Output = ()> + Send {
+    // collected up front, so the future does not hold on to the iterator
+    let directories = paths
+        .into_iter()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .unique()
+        .collect::<Vec<_>>();
+
+    async move {
+        for directory in directories {
+            atomic::remove_stale(&directory).await;
+        }
+    }
+}
+
+/// Streams a file to `path`, reporting the downloaded bytes (capped to the size) to `on_progress`
+/// and verifying the sha1 and size once done. The file is downloaded to a temporary file first,
+/// and only moved to `path` if it matches.
+///
+/// Nothing is downloaded if the file already exists with the right sha1 and size.
+///
+/// # Errors
+/// Errors if the download fails, the file cannot be written, `on_progress` fails, or the sha1 or
+/// size do not match
+pub(crate) async fn download_streamed(
+    client: &reqwest::Client,
+    url: &str,
+    sha1: &str,
+    size: Option<u64>,
+    path: &Path,
+    mut on_progress: impl FnMut(u64) -> Result<(), DownloadError> + Send,
+) -> Result<(), DownloadError> {
+    if is_downloaded(path, sha1, size).await {
+        return Ok(());
+    }
+
+    let response = get(client, url).await?.bytes_stream();
+
+    let parent_dir = path.parent().ok_or(DownloadError::IoError).into_report()?;
+
+    fs::create_dir_all(parent_dir)
+        .await
+        .into_report()
+        .change_context(DownloadError::IoError)?;
+
+    // the file only appears at `path` once complete and verified, so an interrupted download is
+    // never mistaken for a finished one
+    let temp = atomic::temp_path(path);
+    let result = stream_to(&temp, response, sha1, size, &mut on_progress).await;
+
+    let result = match result {
+        Ok(()) => fs::rename(&temp, path)
+            .await
+            .into_report()
+            .change_context(DownloadError::IoError),
+        Err(e) => Err(e.attach_printable(format!("Downloaded from {url}"))),
+    };
+
+    if result.is_err() {
+        // the temporary file may not exist, in which case there is nothing to clean up
+        let _ = fs::remove_file(&temp).await;
+    }
+
+    result
+}
+
+/// Writes a response to `path`, syncing it to disk and verifying it once done
+async fn stream_to(
+    path: &Path,
+    mut response: impl Stream<Item = reqwest::Result<impl AsRef<[u8]>>> + Unpin + Send,
+    sha1: &str,
+    size: Option<u64>,
+    on_progress: &mut (impl FnMut(u64) -> Result<(), DownloadError> + Send),
+) -> Result<(), DownloadError> {
+    let mut file = fs::File::create(path)
+        .await
+        .into_report()
+        .change_context(DownloadError::IoError)?;
+
+    let mut hasher = Sha1::new();
+    let mut downloaded: u64 = 0;
+
+    while let Some(chunk) = response.next().await {
+        let chunk = chunk
+            .into_report()
+            .change_context(DownloadError::ReqwestError)?;
+
+        let chunk = chunk.as_ref();
+
+        file.write_all(chunk)
+            .await
+            .into_report()
+            .change_context(DownloadError::IoError)?;
+
+        hasher.update(chunk);
+        downloaded += chunk.len() as u64;
+
+        on_progress(size.map_or(downloaded, |size| min(downloaded, size)))?;
+    }
+
+    file.sync_all()
+        .await
+        .into_report()
+        .change_context(DownloadError::IoError)?;
+
+    check_integrity(sha1, size, &finalize_hex(hasher), downloaded)
+}
+
+/// How failed downloads are retried.
+///
+/// The delay before each retry doubles from `initial_delay` up to `max_delay`, plus up to 50%
+/// random jitter so concurrent downloads do not all retry at once. Only errors that may go away are
+/// retried, see [`DownloadError::is_retryable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(500), Duration::from_secs(10))
+    }
+}
+
+impl RetryPolicy {
+    #[must_use]
+    pub const fn new(max_retries: u32, initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_delay,
+            max_delay,
+        }
+    }
+
+    /// Never retries
+    #[must_use]
+    pub const fn none() -> Self {
+        Self::new(0, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Gets the delay before the given retry (starting at 0), without jitter
+    #[must_use]
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Runs `download`, retrying it while it fails with a retryable error
+    pub(crate) async fn run<T, F, Fut>(&self, mut download: F) -> Result<T, DownloadError>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T, DownloadError>> + Send,
+    {
+        let mut retry = 0;
+
+        loop {
+            match download().await {
+                Err(e) if retry < self.max_retries && e.current_context().is_retryable() => {
+                    let delay = self.delay(retry).mul_f64(1.0 + fastrand::f64() / 2.0);
+
+                    warn!("Download failed, retrying in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Getter methods
+impl RetryPolicy {
+    #[must_use]
+    pub const fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    #[must_use]
+    pub const fn initial_delay(&self) -> Duration {
+        self.initial_delay
+    }
+
+    #[must_use]
+    pub const fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+}
+
+/// The items that could not be downloaded, when continuing on errors
+#[derive(Debug)]
+pub struct DownloadSummary<T> {
+    failed: Vec<(T, Arc<Report<DownloadError>>)>,
+}
+
+impl<T> Default for DownloadSummary<T> {
+    fn default() -> Self {
+        Self { failed: vec![] }
+    }
+}
+
+impl<T> DownloadSummary<T> {
+    /// Collects the results of downloading items. Failed items are reported through `sender`. If
+    /// not continuing on errors, the first error is returned instead
+    pub(crate) fn collect(
+        results: impl IntoIterator<Item = (T, Result<(), DownloadError>)>,
+        continue_on_error: bool,
+        sender: &UnboundedSender<DownloadMessage<T>>,
+    ) -> Result<Self, DownloadError>
+    where
+        T: Clone + fmt::Debug + Send + Sync + 'static,
+    {
+        let mut summary = Self::default();
+
+        for (item, result) in results {
+            let Err(e) = result else {
+                continue;
+            };
+
+            if !continue_on_error {
+                return Err(e);
+            }
+
+            let error = Arc::new(e);
+
+            sender
+                .send(DownloadMessage::Failed(item.clone(), error.clone()))
+                .into_report()
+                .change_context(DownloadError::ChannelError)?;
+
+            summary.failed.push((item, error));
+        }
+
+        Ok(summary)
+    }
+
+    /// Runs download tasks, at most `max_concurrent` at once, and collects their results like
+    /// [`Self::collect`]. If not continuing on errors, the remaining tasks are aborted as soon as
+    /// one fails
+    pub(crate) async fn collect_tasks<F, R>(
+        tasks: impl IntoIterator<Item = F>,
+        max_concurrent: usize,
+        continue_on_error: bool,
+        sender: &UnboundedSender<DownloadMessage<T>>,
+    ) -> Result<Self, DownloadError>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: IntoIterator<Item = (T, Result<(), DownloadError>)> + Send + 'static,
+        T: Clone + fmt::Debug + Send + Sync + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(max_concurrent));
+        let mut running = JoinSet::new();
+
+        for task in tasks {
+            let semaphore = semaphore.clone();
+
+            running.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                task.await
+            });
+        }
+
+        let mut summary = Self::default();
+
+        // returning early drops `running`, which aborts the tasks that are left
+        while let Some(results) = running.join_next().await {
+            let results = results
+                .into_report()
+                .change_context(DownloadError::JoinError)?;
+
+            summary.extend(Self::collect(results, continue_on_error, sender)?);
+        }
+
+        Ok(summary)
+    }
+
+    /// Converts the items, e.g to combine the summaries of several downloaders
+    #[must_use]
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> DownloadSummary<U> {
+        DownloadSummary {
+            failed: self
+                .failed
+                .into_iter()
+                .map(|(item, error)| (f(item), error))
+                .collect(),
+        }
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.failed.extend(other.failed);
+    }
+
+    #[must_use]
+    pub const fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Gets the items that failed, with why
+    #[must_use]
+    pub fn failed(&self) -> &[(T, Arc<Report<DownloadError>>)] {
+        &self.failed
+    }
+}
+
 #[derive(Debug)]
 pub enum DownloadMessage<T> {
     /// A file was successfully downloaded
@@ -37,6 +532,9 @@ pub enum DownloadMessage<T> {
     DownloadedAll,
     /// (T, downloaded bytes)
     DownloadProgress(T, u64),
+    /// A file could not be downloaded, even after retrying. Only sent when continuing on errors,
+    /// otherwise the download stops with the error
+    Failed(T, Arc<Report<DownloadError>>),
 }
 
 pub trait Downloader {
@@ -49,12 +547,101 @@ pub trait Downloader {
     ///
     /// See the downloaders documentation for more information. Usually appends the path to the
     /// main directory.
-    async fn download(&self, item: Self::DownloadItem) -> Result<(), DownloadError>;
+    fn download(
+        &self,
+        item: Self::DownloadItem,
+    ) -> impl Future<Output = Result<(), DownloadError>> + Send;
 
     /// Downloads all files for this specific downloader. Requires an Arc for multithreading
     /// purposes
     ///
     /// See the downloaders documentation for more information. Usually appends the path to the
     /// main directory.
-    async fn download_all(self: Arc<Self>) -> Result<(), DownloadError>;
+    ///
+    /// When continuing on errors, the items that failed are returned instead of stopping at the
+    /// first error.
+    fn download_all(
+        self: Arc<Self>,
+    ) -> impl Future<Output = Result<DownloadSummary<Self::DownloadItem>, DownloadError>> + Send;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use futures::FutureExt;
+    use test_case::test_case;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn integrity() {
+        let sha1 = sha1_hex(b"hello");
+
+        assert!(check_integrity(&sha1, Some(5), &sha1, 5).is_ok());
+        // installer generated libraries have no sha1
+        assert!(check_integrity("", None, &sha1, 5).is_ok());
+
+        let error = check_integrity(&sha1, Some(5), &sha1_hex(b"world"), 5).unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            DownloadError::IntegrityError { expected, .. } if *expected == sha1
+        ));
+
+        let error = check_integrity(&sha1, Some(4), &sha1, 5).unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            DownloadError::SizeMismatch {
+                expected: 4,
+                actual: 5
+            }
+        ));
+    }
+
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(500), Duration::from_secs(3));
+
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(3));
+        assert_eq!(policy.delay(40), Duration::from_secs(3));
+    }
+
+    #[test_case(&DownloadError::ReqwestError, true)]
+    #[test_case(&DownloadError::StatusError { status: 503 }, true)]
+    #[test_case(&DownloadError::StatusError { status: 429 }, true)]
+    #[test_case(&DownloadError::StatusError { status: 404 }, false)]
+    #[test_case(&DownloadError::IoError, false)]
+    fn retryable(error: &DownloadError, expected: bool) {
+        assert_eq!(error.is_retryable(), expected);
+    }
+
+    #[tokio::test]
+    async fn first_failure_aborts_the_rest() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        let slow = {
+            let finished = finished.clone();
+
+            async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                finished.store(true, Ordering::SeqCst);
+                vec![("slow", Ok(()))]
+            }
+            .boxed()
+        };
+        let failing = async { vec![("failing", Err(Report::new(DownloadError::IoError)))] }.boxed();
+
+        let result = DownloadSummary::collect_tasks([slow, failing], 2, false, &sender).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(matches!(
+            result.unwrap_err().current_context(),
+            DownloadError::IoError
+        ));
+        assert!(!finished.load(Ordering::SeqCst));
+    }
 }
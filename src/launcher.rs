@@ -1,34 +1,41 @@
 use derive_builder::Builder;
 use error_stack::{IntoReport, Result, ResultExt};
 use std::{
+    collections::BTreeMap,
     error::Error as ErrorTrait,
     fmt::{self, Display, Formatter},
     path::PathBuf,
-    process::{ExitStatus, Stdio},
+    process::ExitStatus,
     sync::Arc,
 };
 use tokio::{
+    fs,
     io::BufReader,
-    process::{ChildStderr, ChildStdout, Command},
+    process::{ChildStderr, ChildStdout},
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
-    task::JoinHandle,
+    task::{JoinHandle, JoinSet},
 };
-use tracing::{debug, error};
-
-#[cfg(target_os = "windows")]
-use winsafe::IsWindows10OrGreater;
+use tracing::{debug, error, warn};
 
 use crate::{
     assets::{
-        asset_index::{AssetDownloader, Assets, Object},
-        client::{self, Artifact, ClassDownloader, DownloadClass, Library, LibraryDownloader},
+        asset_index::{AssetDownloader, AssetLayout, Assets, Object},
+        client::{
+            self, Artifact, ClassDownloader, DownloadClass, File, Library, LibraryDownloader,
+        },
     },
     auth::{
         structs::{MinecraftProfile, MinecraftToken},
         MSauth,
     },
-    downloader::{DownloadError, DownloadMessage, Downloader as DownloaderTrait},
-    parser::{JvmArgs, MinecraftArgs},
+    command::LaunchCommand,
+    downloader::{
+        DownloadError, DownloadMessage, DownloadSummary, Downloader as DownloaderTrait, RetryPolicy,
+    },
+    log4j::Log4jMitigation,
+    natives::{LibrarySelection, NativesExtractor},
+    parser::{unresolved_placeholders, JvmArgs, MinecraftArgs, Placeholders},
+    platform::Platform,
 };
 
 #[derive(Debug, Clone)]
@@ -82,6 +89,14 @@ impl Quickplay {
     pub const fn is_realms(&self) -> bool {
         matches!(self, Self::Realms(_))
     }
+
+    /// Gets the world name, server address or realm ID
+    #[must_use]
+    pub fn value(&self) -> &str {
+        match self {
+            Self::Singleplayer(value) | Self::Multiplayer(value) | Self::Realms(value) => value,
+        }
+    }
 }
 
 #[derive(Debug, Builder)]
@@ -99,6 +114,9 @@ pub struct Launcher {
     assets_directory: PathBuf,
     /// The libraries directory, this is the root of the libraries folder
     libraries_directory: PathBuf,
+    /// The directory natives get extracted into (e.g `versions/<version>/natives`). This is wiped
+    /// whenever the natives change, so it should be unique per version
+    natives_directory: PathBuf,
     /// The path to <version>.json
     version_manifest_path: PathBuf,
     /// is this version a snapshot
@@ -115,10 +133,28 @@ pub struct Launcher {
     launcher_version: String,
     /// If you want to launch with quickplay
     quickplay: Option<Quickplay>,
+    /// Where the game logs quickplay activity. Defaults to `<game_directory>/quickPlay/log.json`
+    #[builder(default)]
+    quickplay_path: Option<PathBuf>,
     /// The reqwest client
     http_client: reqwest::Client,
     /// The manifest the launcher will use
     manifest: client::Manifest,
+    /// The platform to launch for. Defaults to the host
+    #[builder(default)]
+    platform: Platform,
+    /// Whether to protect versions using an affected log4j against CVE-2021-44228. Defaults to true
+    #[builder(default = "true")]
+    mitigate_log4shell: bool,
+    /// The client ID sent to the game as `${clientid}`, used for telemetry
+    #[builder(default)]
+    client_id: Option<String>,
+    /// The xbox user ID sent to the game as `${auth_xuid}`, used for telemetry
+    #[builder(default)]
+    xuid: Option<String>,
+    /// Extra environment variables to launch the game with
+    #[builder(default)]
+    environment: BTreeMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -127,6 +163,8 @@ pub enum Error {
     CannotGetStderr,
     ProcessError,
     AuthError,
+    NativesError,
+    AssetsError,
 }
 
 impl Display for Error {
@@ -136,6 +174,8 @@ impl Display for Error {
             Self::CannotGetStderr => "Cannot get stderr",
             Self::ProcessError => "Process error",
             Self::AuthError => "Authentication Error",
+            Self::NativesError => "Could not find or extract natives",
+            Self::AssetsError => "Could not set up assets",
         })
     }
 }
@@ -144,10 +184,12 @@ impl ErrorTrait for Error {}
 
 /// High-level API
 impl Launcher {
-    /// Launches the game, assuming all the required files are downloaded
+    /// Launches the game, assuming all the required files are downloaded. Natives are extracted
+    /// and assets copied for pre-1.7 versions first if needed
     ///
     /// # Errors
-    /// If the process cannot be spawned, or the stdout/stderr cannot be read
+    /// If the natives cannot be extracted, the process cannot be spawned, or the stdout/stderr
+    /// cannot be read
     #[tracing::instrument(skip(self))]
     pub async fn launch(&mut self) -> Result<GameOutput, Error> {
         debug!("Launching game");
@@ -160,26 +202,39 @@ impl Launcher {
                 .change_context(Error::AuthError)?;
         }
 
-        let mut game_args = MinecraftArgs::new(self, &self.manifest).parse_minecraft_args();
-        let mut jvm_args = JvmArgs::new(self, &self.manifest).parse_jvm_args();
+        LibrarySelection::new(self.manifest.libraries(), &self.platform)
+            .into_result()
+            .change_context(Error::NativesError)?;
 
-        debug!("Game args: {:?}", game_args);
-        debug!("JVM args: {:?}", jvm_args);
+        NativesExtractor::new(
+            self.libraries_directory.clone(),
+            self.natives_directory.clone(),
+            self.platform.clone(),
+        )
+        .extract(self.manifest.libraries())
+        .await
+        .change_context(Error::NativesError)?;
 
-        let main_class = self.manifest.main_class();
+        let assets = self.load_assets().await;
+        let layout = assets.as_ref().map_or(AssetLayout::Objects, Assets::layout);
 
-        debug!("Main class: {}", main_class);
+        if let Some(assets) = &assets {
+            self.reconstruct_assets(assets).await?;
+        }
 
-        let mut process = Command::new(self.java_path.clone())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .args(&mut jvm_args)
-            .arg(main_class)
-            .args(&mut game_args)
-            .spawn()
+        debug!("Log4Shell mitigation: {:?}", self.log4j_mitigation());
+
+        // the game runs in its game directory, like with the vanilla launcher
+        fs::create_dir_all(&self.game_directory)
+            .await
             .into_report()
             .change_context(Error::ProcessError)?;
 
+        let mut process = self
+            .command_for_layout(layout)
+            .spawn()
+            .change_context(Error::ProcessError)?;
+
         let stdout = process.stdout.take().ok_or(Error::CannotGetStdout)?;
 
         let stderr = process.stderr.take().ok_or(Error::CannotGetStderr)?;
@@ -197,11 +252,131 @@ impl Launcher {
     }
 }
 
-#[derive(Debug)]
+/// Command
+impl Launcher {
+    /// Builds the command [`Launcher::launch`] runs, without running it. It runs in the game
+    /// directory. Sensitive arguments like the access token are masked when the command is printed
+    /// or rendered
+    ///
+    /// The saved asset index is read to know where the game reads its assets from
+    pub async fn command(&self) -> LaunchCommand {
+        let layout = self
+            .load_assets()
+            .await
+            .map_or(AssetLayout::Objects, |assets| assets.layout());
+
+        self.command_for_layout(layout)
+    }
+
+    fn command_for_layout(&self, layout: AssetLayout) -> LaunchCommand {
+        // building the classpath canonicalizes every library, so this is only done once
+        let placeholders = Placeholders::for_launch(self, &self.manifest, layout);
+        let game_args =
+            MinecraftArgs::new(self, &self.manifest, &placeholders).parse_minecraft_args();
+        let jvm_args = JvmArgs::new(self, &self.manifest, &placeholders).parse_jvm_args();
+
+        debug!("Game args: {:?}", game_args);
+        debug!("JVM args: {:?}", jvm_args);
+
+        let unresolved = unresolved_placeholders(&[jvm_args.as_slice(), &game_args].concat());
+
+        if !unresolved.is_empty() {
+            warn!(
+                "Unresolved placeholders in arguments: {}",
+                unresolved.join(", ")
+            );
+        }
+
+        LaunchCommand::new(
+            self.java_path.clone(),
+            jvm_args,
+            self.manifest.main_class().to_owned(),
+            game_args,
+        )
+        .with_working_directory(self.game_directory.clone())
+        .with_environment(self.environment.clone())
+    }
+}
+
+/// Assets
+impl Launcher {
+    /// Gets where the asset index is saved, see [`client::AssetIndex::save`]
+    #[must_use]
+    pub fn asset_index_path(&self) -> PathBuf {
+        self.manifest.asset_index().path(&self.assets_directory)
+    }
+
+    /// Gets the directory the game reads assets from with the given layout, used for
+    /// `${game_assets}`
+    #[must_use]
+    pub fn game_assets_directory(&self, layout: AssetLayout) -> PathBuf {
+        layout.game_assets_directory(
+            self.manifest.asset_index().id(),
+            &self.assets_directory,
+            &self.game_directory,
+        )
+    }
+
+    /// Loads the saved asset index, warning if it is missing
+    async fn load_assets(&self) -> Option<Assets> {
+        Assets::load(&self.asset_index_path())
+            .await
+            .map_err(|e| warn!("Could not read the asset index, assets may be missing: {e:?}"))
+            .ok()
+    }
+
+    /// Copies the assets to where pre-1.7 versions expect them, if needed
+    async fn reconstruct_assets(&self, assets: &Assets) -> Result<(), Error> {
+        if assets.layout() == AssetLayout::Objects {
+            return Ok(());
+        }
+
+        let target = self.game_assets_directory(assets.layout());
+
+        debug!(
+            "Copying {:?} assets to {}",
+            assets.layout(),
+            target.display()
+        );
+
+        assets
+            .reconstruct(&self.assets_directory, &target)
+            .await
+            .change_context(Error::AssetsError)
+    }
+}
+
+/// Logging
+impl Launcher {
+    /// Reports how the game is protected against CVE-2021-44228, or `None` if the mitigation is
+    /// disabled
+    #[must_use]
+    pub fn log4j_mitigation(&self) -> Option<Log4jMitigation> {
+        self.mitigate_log4shell
+            .then(|| Log4jMitigation::for_manifest(&self.manifest))
+    }
+
+    /// The logging config the game is launched with. This is a patched config if the version
+    /// needs one, otherwise the manifest's own config
+    #[must_use]
+    pub fn logging_config(&self) -> Option<File> {
+        match self.log4j_mitigation() {
+            Some(Log4jMitigation::PatchedConfig(file)) => Some(file),
+            _ => self
+                .manifest
+                .logging()
+                .map(|logging| logging.client().file().clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum DownloadItem {
     Asset(Object),
     Library(Artifact),
     Client(DownloadClass),
+    /// The log4j config passed to the game with the logging argument
+    Logging(File),
 }
 
 #[derive(Debug, Clone)]
@@ -212,6 +387,13 @@ pub struct Downloader {
     asset_downloader: AssetDownloader,
     library_downloader: LibraryDownloader,
     client_download: ClassDownloader,
+
+    logging: Option<File>,
+    assets_directory: PathBuf,
+    http_client: reqwest::Client,
+
+    retry_policy: RetryPolicy,
+    continue_on_error: bool,
 }
 
 impl Downloader {
@@ -234,7 +416,8 @@ impl Downloader {
             launcher.libraries_directory.clone(),
             launcher.http_client.clone(),
             max_concurrent_downloads / 2,
-        );
+        )
+        .with_platform(launcher.platform.clone());
 
         let class_downloader = ClassDownloader::new(
             launcher.http_client.clone(),
@@ -247,8 +430,41 @@ impl Downloader {
             asset_downloader,
             library_downloader,
             client_download: class_downloader,
+            logging: launcher.logging_config(),
+            assets_directory: launcher.assets_directory.clone(),
+            http_client: launcher.http_client.clone(),
+            retry_policy: RetryPolicy::default(),
+            continue_on_error: false,
         }
     }
+
+    /// Sets how failed downloads are retried, for every file
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.asset_downloader = self
+            .asset_downloader
+            .with_retry_policy(retry_policy.clone());
+        self.library_downloader = self
+            .library_downloader
+            .with_retry_policy(retry_policy.clone());
+        self.client_download = self.client_download.with_retry_policy(retry_policy.clone());
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Keeps downloading the other files when one fails, see [`DownloadSummary`]. The client jar
+    /// is always required
+    #[must_use]
+    pub fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.asset_downloader = self
+            .asset_downloader
+            .with_continue_on_error(continue_on_error);
+        self.library_downloader = self
+            .library_downloader
+            .with_continue_on_error(continue_on_error);
+        self.continue_on_error = continue_on_error;
+        self
+    }
 }
 
 /// Downloads _everything_ needed to launch the game
@@ -298,6 +514,19 @@ impl DownloaderTrait for Downloader {
                             );
                         }
                     }
+                    DownloadMessage::Failed(object, report) => {
+                        debug!("Asset downloader object {} failed", object.hash());
+                        let hash = object.hash().to_owned();
+
+                        if let Err(e) = asset_sender
+                            .send(DownloadMessage::Failed(DownloadItem::Asset(object), report))
+                        {
+                            error!(
+                                "Asset downloader failed to send object {} failed: {}",
+                                hash, e
+                            );
+                        }
+                    }
                     DownloadMessage::DownloadedAll => {
                         debug!("Asset downloader finished downloading all assets");
                     }
@@ -343,6 +572,20 @@ impl DownloaderTrait for Downloader {
                             );
                         }
                     }
+                    DownloadMessage::Failed(object, report) => {
+                        debug!("Library downloader object {} failed", object.sha1());
+                        let hash = object.sha1().to_owned();
+
+                        if let Err(e) = library_sender.send(DownloadMessage::Failed(
+                            DownloadItem::Library(object),
+                            report,
+                        )) {
+                            error!(
+                                "Library downloader failed to send object {} failed: {}",
+                                hash, e
+                            );
+                        }
+                    }
                     DownloadMessage::DownloadedAll => {
                         debug!("Library downloader finished downloading all assets");
                     }
@@ -376,6 +619,16 @@ impl DownloaderTrait for Downloader {
                             error!("Client downloader failed to send progress: {}", e);
                         }
                     }
+                    DownloadMessage::Failed(object, report) => {
+                        debug!("Client failed");
+
+                        if let Err(e) = class_sender.send(DownloadMessage::Failed(
+                            DownloadItem::Client(object),
+                            report,
+                        )) {
+                            error!("Client downloader failed to send failed: {}", e);
+                        }
+                    }
                     DownloadMessage::DownloadedAll => {
                         debug!("Client downloader finished downloading client");
                     }
@@ -391,26 +644,66 @@ impl DownloaderTrait for Downloader {
             DownloadItem::Asset(object) => self.asset_downloader.download(object).await,
             DownloadItem::Library(library) => self.library_downloader.download(library).await,
             DownloadItem::Client(client) => self.client_download.download(client).await,
+            DownloadItem::Logging(file) => self
+                .retry_policy
+                .run(|| file.download(&self.assets_directory, &self.http_client))
+                .await
+                .map(|_| ()),
         }
     }
 
-    async fn download_all(self: Arc<Self>) -> Result<(), DownloadError> {
+    async fn download_all(
+        self: Arc<Self>,
+    ) -> Result<DownloadSummary<Self::DownloadItem>, DownloadError> {
         let asset_downloader = Arc::new(self.asset_downloader.clone());
         let library_downloader = Arc::new(self.library_downloader.clone());
         let client_downloader = Arc::new(self.client_download.clone());
 
-        let assets = tokio::spawn(async move { asset_downloader.download_all().await });
-        let libraries = tokio::spawn(async move { library_downloader.download_all().await });
-        let client = tokio::spawn(async move { client_downloader.download_all().await });
+        let mut downloaders = JoinSet::new();
+        downloaders.spawn(async move {
+            let summary = asset_downloader.download_all().await;
+            summary.map(|summary| summary.map(DownloadItem::Asset))
+        });
+        downloaders.spawn(async move {
+            let summary = library_downloader.download_all().await;
+            summary.map(|summary| summary.map(DownloadItem::Library))
+        });
+        downloaders.spawn(async move {
+            let summary = client_downloader.download_all().await;
+            summary.map(|summary| summary.map(DownloadItem::Client))
+        });
 
-        // wait for both to finish
-        let (assets, libraries, client) = tokio::try_join!(assets, libraries, client)
-            .into_report()
-            .change_context(DownloadError::JoinError)?;
+        // failures of the sub downloaders were already forwarded by the channel. Returning early
+        // drops `downloaders`, which aborts the ones still running
+        let mut summary = DownloadSummary::default();
 
-        assets?;
-        libraries?;
-        client?;
+        while let Some(result) = downloaders.join_next().await {
+            summary.extend(
+                result
+                    .into_report()
+                    .change_context(DownloadError::JoinError)??,
+            );
+        }
+
+        if let Some(file) = &self.logging {
+            let item = DownloadItem::Logging(file.clone());
+            let result = self.download(item.clone()).await;
+
+            if result.is_ok() {
+                self.sender
+                    .as_ref()
+                    .unwrap()
+                    .send(DownloadMessage::Downloaded(item.clone()))
+                    .into_report()
+                    .change_context(DownloadError::ChannelError)?;
+            }
+
+            summary.extend(DownloadSummary::collect(
+                [(item, result)],
+                self.continue_on_error,
+                self.sender.as_ref().unwrap(),
+            )?);
+        }
 
         self.sender
             .as_ref()
@@ -419,7 +712,7 @@ impl DownloaderTrait for Downloader {
             .into_report()
             .change_context(DownloadError::ChannelError)?;
 
-        Ok(())
+        Ok(summary)
     }
 }
 
@@ -455,6 +748,11 @@ impl Launcher {
         &self.libraries_directory
     }
 
+    #[must_use]
+    pub const fn natives_directory(&self) -> &PathBuf {
+        &self.natives_directory
+    }
+
     #[must_use]
     pub const fn version_manifest_path(&self) -> &PathBuf {
         &self.version_manifest_path
@@ -494,4 +792,36 @@ impl Launcher {
     pub const fn quickplay(&self) -> Option<&Quickplay> {
         self.quickplay.as_ref()
     }
+
+    #[must_use]
+    pub fn quickplay_path(&self) -> PathBuf {
+        self.quickplay_path
+            .clone()
+            .unwrap_or_else(|| self.game_directory.join("quickPlay").join("log.json"))
+    }
+
+    #[must_use]
+    pub const fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    #[must_use]
+    pub const fn mitigate_log4shell(&self) -> bool {
+        self.mitigate_log4shell
+    }
+
+    #[must_use]
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn xuid(&self) -> Option<&str> {
+        self.xuid.as_deref()
+    }
+
+    #[must_use]
+    pub const fn environment(&self) -> &BTreeMap<String, String> {
+        &self.environment
+    }
 }
@@ -1,10 +1,20 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)]
-#![feature(async_fn_in_trait)]
 
 pub mod assets;
+mod atomic;
 pub mod auth;
+pub mod command;
+pub mod deobfuscator;
 pub mod downloader;
 pub mod launcher;
+pub mod log4j;
+pub mod mappings;
 pub mod merger;
+pub mod natives;
 pub mod parser;
+pub mod platform;
+pub mod resolver;
+pub mod rules;
+pub mod server;
+pub mod validator;
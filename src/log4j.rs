@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use crate::assets::client::{self, Args, File, Jvm, Library};
+
+/// Disables message lookups, which is enough to mitigate CVE-2021-44228 on log4j 2.10+
+pub const NO_LOOKUPS_PROPERTY: &str = "-Dlog4j2.formatMsgNoLookups=true";
+
+/// Mojang's patched config for 1.7 to 1.11.2 (log4j 2.0-beta9)
+const PATCHED_CONFIG_17_111: (&str, &str) = (
+    "log4j2_17-111.xml",
+    "4bb89a97a66f350bc9f73b3ca8509632682aea2e",
+);
+
+/// Mojang's patched config for 1.12 to 1.16.5 (log4j 2.8.1)
+const PATCHED_CONFIG_112_116: (&str, &str) = (
+    "log4j2_112-116.xml",
+    "02937d122c86ce73319ef9975b58896fc1b491d1",
+);
+
+/// How a version is protected against CVE-2021-44228 (log4shell)
+#[derive(Debug, Clone)]
+pub enum Log4jMitigation {
+    /// The version does not use an affected log4j, or the manifest already mitigates it
+    NotAffected,
+    /// log4j 2.10 to 2.14.1, where lookups are disabled with [`NO_LOOKUPS_PROPERTY`]
+    SystemProperty,
+    /// log4j older than 2.10, where the logging config is replaced with a patched one. The file
+    /// is downloaded into `log_configs` like the manifest's own config
+    PatchedConfig(File),
+}
+
+impl Log4jMitigation {
+    /// Detects the mitigation a manifest needs from the version of its `log4j-core` library
+    #[must_use]
+    pub fn for_manifest(manifest: &client::Manifest) -> Self {
+        let mitigation = Self::for_libraries(manifest.libraries());
+
+        let has_property = match manifest.get_arguments() {
+            Args::Arguments(arguments) => arguments
+                .jvm()
+                .iter()
+                .any(|arg| matches!(arg, Jvm::String(arg) if arg == NO_LOOKUPS_PROPERTY)),
+            Args::MinecraftArguments(_) => false,
+        };
+
+        match mitigation {
+            Self::SystemProperty if has_property => Self::NotAffected,
+            mitigation => mitigation,
+        }
+    }
+
+    /// Detects the mitigation needed for the given libraries
+    #[must_use]
+    pub fn for_libraries(libraries: &[Library]) -> Self {
+        let version = libraries
+            .iter()
+            .filter_map(Library::parse_name)
+            .find(|coordinate| {
+                coordinate.group() == "org.apache.logging.log4j"
+                    && coordinate.artifact() == "log4j-core"
+            })
+            .and_then(|coordinate| parse_version(coordinate.version()));
+
+        match version {
+            // 1.7 to 1.11.2 use 2.0-beta9, while 1.12 to 1.16.5 use 2.8.1
+            Some((2, minor)) if minor < 8 => Self::patched_config(PATCHED_CONFIG_17_111),
+            Some((2, minor)) if minor < 10 => Self::patched_config(PATCHED_CONFIG_112_116),
+            Some((2, minor)) if minor < 15 => Self::SystemProperty,
+            _ => Self::NotAffected,
+        }
+    }
+
+    fn patched_config((id, sha1): (&str, &str)) -> Self {
+        let url = format!("https://launcher.mojang.com/v1/objects/{sha1}/{id}");
+
+        Self::PatchedConfig(File::new(id, sha1, None, &url))
+    }
+
+    /// The JVM arguments applying the mitigation. For patched configs, this replaces the
+    /// manifest's logging argument
+    #[must_use]
+    pub fn jvm_arguments(&self, assets_directory: &Path) -> Vec<String> {
+        match self {
+            Self::NotAffected => vec![],
+            Self::SystemProperty => vec![NO_LOOKUPS_PROPERTY.to_owned()],
+            Self::PatchedConfig(file) => vec![format!(
+                "-Dlog4j.configurationFile={}",
+                file.path(assets_directory).display()
+            )],
+        }
+    }
+}
+
+/// Parses the major and minor version of a log4j version like `2.0-beta9` or `2.14.1`
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.').map(|part| {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(part, |end| &part[..end]);
+
+        digits.parse::<u32>().ok()
+    });
+
+    Some((parts.next()??, parts.next()??))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use test_case::test_case;
+
+    fn libraries(log4j: &str) -> Vec<Library> {
+        serde_json::from_value(json!([
+            { "name": "org.apache.logging.log4j:log4j-api:2.8.1", "downloads": {} },
+            { "name": format!("org.apache.logging.log4j:log4j-core:{log4j}"), "downloads": {} },
+        ]))
+        .unwrap()
+    }
+
+    #[test_case("2.0-beta9", Some("log4j2_17-111.xml"); "1.7")]
+    #[test_case("2.8.1", Some("log4j2_112-116.xml"); "1.12")]
+    fn patched_config(log4j: &str, config: Option<&str>) {
+        let mitigation = Log4jMitigation::for_libraries(&libraries(log4j));
+
+        let id = match &mitigation {
+            Log4jMitigation::PatchedConfig(file) => Some(file.id()),
+            _ => None,
+        };
+
+        assert_eq!(id, config);
+    }
+
+    #[test_case("2.14.1", true; "1.18")]
+    #[test_case("2.17.0", false; "1.18.1")]
+    fn system_property(log4j: &str, needed: bool) {
+        let mitigation = Log4jMitigation::for_libraries(&libraries(log4j));
+
+        assert_eq!(
+            matches!(mitigation, Log4jMitigation::SystemProperty),
+            needed
+        );
+    }
+
+    #[test_case(&["-Xss1M", NO_LOOKUPS_PROPERTY], &[]; "already mitigated")]
+    #[test_case(&["-Xss1M"], &[NO_LOOKUPS_PROPERTY]; "not mitigated")]
+    fn manifest_property_is_not_duplicated(jvm: &[&str], added: &[&str]) {
+        let manifest: client::Manifest = serde_json::from_value(json!({
+            "assetIndex": {
+                "id": "1.18",
+                "sha1": "a",
+                "size": 1,
+                "totalSize": 2,
+                "url": "https://piston-meta.mojang.com/1.18.json"
+            },
+            "assets": "1.18",
+            "downloads": {
+                "client": { "sha1": "b", "size": 3, "url": "https://piston-data.mojang.com/client.jar" },
+                "server": { "sha1": "c", "size": 4, "url": "https://piston-data.mojang.com/server.jar" }
+            },
+            "id": "1.18",
+            "libraries": libraries("2.14.1"),
+            "mainClass": "net.minecraft.client.main.Main",
+            "arguments": { "game": [], "jvm": jvm },
+            "minimumLauncherVersion": 21,
+            "releaseTime": "2021-11-30T09:16:29+00:00",
+            "time": "2021-11-30T09:16:29+00:00",
+            "type": "release"
+        }))
+        .unwrap();
+
+        let mitigation = Log4jMitigation::for_manifest(&manifest);
+
+        assert_eq!(mitigation.jvm_arguments(Path::new("assets")), added);
+    }
+
+    #[test]
+    fn no_log4j() {
+        assert!(matches!(
+            Log4jMitigation::for_libraries(&[]),
+            Log4jMitigation::NotAffected
+        ));
+    }
+}
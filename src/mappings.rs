@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    path::Path,
+};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use tokio::fs;
+
+use crate::assets::client::Mappings;
+
+#[derive(Debug)]
+pub enum MappingsError {
+    /// A line is neither a comment, a class nor a member
+    InvalidLine,
+    /// A member appears before any class
+    MemberWithoutClass,
+    DownloadError,
+    IoError,
+}
+
+impl Display for MappingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidLine => "Invalid line in mappings",
+            Self::MemberWithoutClass => "Member mapping outside of a class",
+            Self::DownloadError => "Could not download mappings",
+            Self::IoError => "Failed during IO task",
+        })
+    }
+}
+
+impl Error for MappingsError {}
+
+/// An inclusive range of line numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl LineRange {
+    #[must_use]
+    pub const fn contains(&self, line: u32) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMapping {
+    field_type: String,
+    named: String,
+    obfuscated: String,
+}
+
+/// Getter methods
+impl FieldMapping {
+    #[must_use]
+    pub fn field_type(&self) -> &str {
+        &self.field_type
+    }
+
+    #[must_use]
+    pub fn named(&self) -> &str {
+        &self.named
+    }
+
+    #[must_use]
+    pub fn obfuscated(&self) -> &str {
+        &self.obfuscated
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodMapping {
+    return_type: String,
+    named: String,
+    obfuscated: String,
+    arguments: Vec<String>,
+    /// The lines of the method in the obfuscated jar, which stack traces refer to
+    lines: Option<LineRange>,
+    /// The lines in the original source, if the method was inlined
+    original_lines: Option<LineRange>,
+}
+
+/// Getter methods
+impl MethodMapping {
+    #[must_use]
+    pub fn return_type(&self) -> &str {
+        &self.return_type
+    }
+
+    #[must_use]
+    pub fn named(&self) -> &str {
+        &self.named
+    }
+
+    #[must_use]
+    pub fn obfuscated(&self) -> &str {
+        &self.obfuscated
+    }
+
+    #[must_use]
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    #[must_use]
+    pub const fn lines(&self) -> Option<LineRange> {
+        self.lines
+    }
+
+    #[must_use]
+    pub const fn original_lines(&self) -> Option<LineRange> {
+        self.original_lines
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassMapping {
+    named: String,
+    obfuscated: String,
+    fields: Vec<FieldMapping>,
+    methods: Vec<MethodMapping>,
+}
+
+impl ClassMapping {
+    #[must_use]
+    pub fn field_by_obfuscated(&self, obfuscated: &str) -> Option<&FieldMapping> {
+        self.fields
+            .iter()
+            .find(|field| field.obfuscated == obfuscated)
+    }
+
+    #[must_use]
+    pub fn field_by_named(&self, named: &str) -> Option<&FieldMapping> {
+        self.fields.iter().find(|field| field.named == named)
+    }
+
+    /// Gets the methods with the given obfuscated name. Methods with different signatures can
+    /// share a name, so there may be more than one
+    pub fn methods_by_obfuscated<'a>(
+        &'a self,
+        obfuscated: &'a str,
+    ) -> impl Iterator<Item = &'a MethodMapping> {
+        self.methods
+            .iter()
+            .filter(move |method| method.obfuscated == obfuscated)
+    }
+
+    /// Gets the methods with the given name, one per overload
+    pub fn methods_by_named<'a>(
+        &'a self,
+        named: &'a str,
+    ) -> impl Iterator<Item = &'a MethodMapping> {
+        self.methods
+            .iter()
+            .filter(move |method| method.named == named)
+    }
+}
+
+/// Getter methods
+impl ClassMapping {
+    #[must_use]
+    pub fn named(&self) -> &str {
+        &self.named
+    }
+
+    #[must_use]
+    pub fn obfuscated(&self) -> &str {
+        &self.obfuscated
+    }
+
+    #[must_use]
+    pub fn fields(&self) -> &[FieldMapping] {
+        &self.fields
+    }
+
+    #[must_use]
+    pub fn methods(&self) -> &[MethodMapping] {
+        &self.methods
+    }
+}
+
+/// Proguard mappings, the format of the `client_mappings` and `server_mappings` downloads
+#[derive(Debug, Clone, Default)]
+pub struct ProguardMappings {
+    classes: Vec<ClassMapping>,
+    by_obfuscated: HashMap<String, usize>,
+    by_named: HashMap<String, usize>,
+}
+
+impl ProguardMappings {
+    /// Parses a Proguard mappings file
+    ///
+    /// # Errors
+    /// Errors if a line cannot be parsed
+    pub fn parse(mappings: &str) -> Result<Self, MappingsError> {
+        let mut classes = Vec::<ClassMapping>::new();
+
+        for (index, line) in mappings.lines().enumerate() {
+            let invalid = |error: MappingsError| {
+                Report::new(error).attach_printable(format!("Line {}: {line}", index + 1))
+            };
+
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(char::is_whitespace) {
+                let class = parse_class(line).ok_or_else(|| invalid(MappingsError::InvalidLine))?;
+                classes.push(class);
+                continue;
+            }
+
+            let class = classes
+                .last_mut()
+                .ok_or_else(|| invalid(MappingsError::MemberWithoutClass))?;
+
+            let (member, obfuscated) = line
+                .trim()
+                .split_once(" -> ")
+                .ok_or_else(|| invalid(MappingsError::InvalidLine))?;
+
+            if member.contains('(') {
+                let method = parse_method(member, obfuscated)
+                    .ok_or_else(|| invalid(MappingsError::InvalidLine))?;
+                class.methods.push(method);
+            } else {
+                let (field_type, named) = member
+                    .split_once(' ')
+                    .ok_or_else(|| invalid(MappingsError::InvalidLine))?;
+
+                class.fields.push(FieldMapping {
+                    field_type: field_type.to_owned(),
+                    named: named.to_owned(),
+                    obfuscated: obfuscated.to_owned(),
+                });
+            }
+        }
+
+        Ok(Self::from_classes(classes))
+    }
+
+    /// Downloads the mappings of a version (e.g `client_mappings`) to `path` and parses them
+    ///
+    /// # Errors
+    /// Errors if the mappings cannot be downloaded, read or parsed
+    pub async fn fetch(
+        mappings: &Mappings,
+        path: &Path,
+        client: &reqwest::Client,
+    ) -> Result<Self, MappingsError> {
+        mappings
+            .download(path, client)
+            .await
+            .change_context(MappingsError::DownloadError)?;
+
+        let contents = fs::read_to_string(path)
+            .await
+            .into_report()
+            .change_context(MappingsError::IoError)?;
+
+        Self::parse(&contents)
+    }
+
+    fn from_classes(classes: Vec<ClassMapping>) -> Self {
+        let by_obfuscated = classes
+            .iter()
+            .enumerate()
+            .map(|(index, class)| (class.obfuscated.clone(), index))
+            .collect();
+
+        let by_named = classes
+            .iter()
+            .enumerate()
+            .map(|(index, class)| (class.named.clone(), index))
+            .collect();
+
+        Self {
+            classes,
+            by_obfuscated,
+            by_named,
+        }
+    }
+
+    /// Gets a class by its obfuscated name, e.g `fkx` or `fkx$a`
+    #[must_use]
+    pub fn class_by_obfuscated(&self, obfuscated: &str) -> Option<&ClassMapping> {
+        self.by_obfuscated
+            .get(obfuscated)
+            .map(|index| &self.classes[*index])
+    }
+
+    /// Gets a class by its fully qualified name, e.g `net.minecraft.client.Minecraft`
+    #[must_use]
+    pub fn class_by_named(&self, named: &str) -> Option<&ClassMapping> {
+        self.by_named.get(named).map(|index| &self.classes[*index])
+    }
+
+    #[must_use]
+    pub fn classes(&self) -> &[ClassMapping] {
+        &self.classes
+    }
+}
+
+/// Parses `net.minecraft.client.Minecraft -> enn:`
+fn parse_class(line: &str) -> Option<ClassMapping> {
+    let (named, obfuscated) = line.strip_suffix(':')?.split_once(" -> ")?;
+
+    Some(ClassMapping {
+        named: named.to_owned(),
+        obfuscated: obfuscated.to_owned(),
+        fields: vec![],
+        methods: vec![],
+    })
+}
+
+/// Parses `12:34:void tick(int,java.lang.String):56:78`, where both line ranges are optional
+fn parse_method(member: &str, obfuscated: &str) -> Option<MethodMapping> {
+    let (lines, signature) = match member.split_once(':') {
+        Some((start, rest)) if start.chars().all(|c| c.is_ascii_digit()) => {
+            let (end, signature) = rest.split_once(':')?;
+            (Some(parse_range(start, end)?), signature)
+        }
+        _ => (None, member),
+    };
+
+    let (signature, original_lines) = match signature.split_once("):") {
+        Some((signature, original)) => {
+            let (start, end) = original.split_once(':').unwrap_or((original, original));
+            (signature, Some(parse_range(start, end)?))
+        }
+        None => (signature.strip_suffix(')')?, None),
+    };
+
+    let (declaration, arguments) = signature.split_once('(')?;
+    let (return_type, named) = declaration.split_once(' ')?;
+
+    Some(MethodMapping {
+        return_type: return_type.to_owned(),
+        named: named.to_owned(),
+        obfuscated: obfuscated.to_owned(),
+        arguments: arguments
+            .split(',')
+            .filter(|argument| !argument.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+        lines,
+        original_lines,
+    })
+}
+
+fn parse_range(start: &str, end: &str) -> Option<LineRange> {
+    Some(LineRange {
+        start: start.parse().ok()?,
+        end: end.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: &str = "\
+# {\"id\":\"sourceFile\",\"fileName\":\"Minecraft.java\"}
+net.minecraft.client.Minecraft -> enn:
+    java.lang.String instance -> a
+    1:5:void <init>() -> <init>
+    10:20:void tick() -> e
+    21:21:void tick(int):300:300 -> e
+    boolean isDemo() -> f
+net.minecraft.client.Minecraft$ChatStatus -> enn$a:
+    net.minecraft.client.Minecraft$ChatStatus ENABLED -> a
+";
+
+    #[test]
+    fn classes() {
+        let mappings = ProguardMappings::parse(MAPPINGS).unwrap();
+
+        assert_eq!(
+            mappings.class_by_obfuscated("enn$a").unwrap().named(),
+            "net.minecraft.client.Minecraft$ChatStatus"
+        );
+        assert_eq!(
+            mappings
+                .class_by_named("net.minecraft.client.Minecraft")
+                .unwrap()
+                .obfuscated(),
+            "enn"
+        );
+    }
+
+    #[test]
+    fn members() {
+        let mappings = ProguardMappings::parse(MAPPINGS).unwrap();
+        let class = mappings.class_by_obfuscated("enn").unwrap();
+
+        assert_eq!(class.field_by_obfuscated("a").unwrap().named(), "instance");
+        assert_eq!(
+            class.field_by_named("instance").unwrap().field_type(),
+            "java.lang.String"
+        );
+
+        let ticks = class.methods_by_obfuscated("e").collect::<Vec<_>>();
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks[0].lines(), Some(LineRange { start: 10, end: 20 }));
+        assert_eq!(ticks[1].arguments(), ["int"]);
+        assert_eq!(
+            ticks[1].original_lines(),
+            Some(LineRange {
+                start: 300,
+                end: 300
+            })
+        );
+
+        let is_demo = class.methods_by_named("isDemo").next().unwrap();
+        assert_eq!(is_demo.obfuscated(), "f");
+        assert_eq!(is_demo.lines(), None);
+    }
+
+    #[test]
+    fn invalid_line() {
+        assert!(ProguardMappings::parse("    int a -> b").is_err());
+        assert!(ProguardMappings::parse("not a mapping").is_err());
+    }
+}
@@ -1,50 +1,247 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
 use serde::{Deserialize, Serialize};
+use serde_json::Map;
 
 use crate::assets::client::{
-    Arguments, AssetIndex, Downloads, JavaVersion, Library, Logging, Type,
+    self, Arguments, AssetIndex, Downloads, Game, JavaVersion, Library, Logging, OutputFormat,
+    SaveError, Type,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase", default)]
 pub struct MergableManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
     asset_index: Option<AssetIndex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     assets: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     downloads: Option<Downloads>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     id: Option<String>,
     /// Default to be empty
     libraries: Vec<Library>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     main_class: Option<String>,
     /// Used before 1.13. Already optional.
+    #[serde(skip_serializing_if = "Option::is_none")]
     minecraft_arguments: Option<String>,
     /// Already optional. When merging, this is preferred over `minecraft_arguments`
+    #[serde(skip_serializing_if = "Option::is_none")]
     arguments: Option<Arguments>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     minimum_launcher_version: Option<i64>,
     /// Already optional
+    #[serde(skip_serializing_if = "Option::is_none")]
     java_version: Option<JavaVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     release_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     time: Option<String>,
     #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     manifest_type: Option<Type>,
     /// Already optional
+    #[serde(skip_serializing_if = "Option::is_none")]
     compliance_level: Option<u8>,
     /// Already optional
+    #[serde(skip_serializing_if = "Option::is_none")]
     logging: Option<Logging>,
     /// Already optional.
+    #[serde(skip_serializing_if = "Option::is_none")]
     inherits_from: Option<String>,
+    /// Fields copper does not model (e.g loader specific keys), kept so saving is lossless
+    #[serde(flatten)]
+    extra: Map<String, serde_json::Value>,
 }
 
-impl MergableManifest {
-    pub fn merge_with(&mut self, other: Self) {
-        // arguments (vector merging)
-        if let Some(arguments) = other.arguments {
-            let mut current_arguments = self.arguments.take().unwrap_or_default();
+#[derive(Debug)]
+pub enum ConversionError {
+    /// A field required by [`client::Manifest`] was not present anywhere in the merged chain
+    MissingField(&'static str),
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field) => {
+                write!(f, "Merged manifest is missing required field `{field}`")
+            }
+        }
+    }
+}
+
+impl Error for ConversionError {}
+
+/// A parent library that was dropped in favour of a different version from the child
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplacedLibrary {
+    /// The full name of the child's library, which is kept
+    pub kept: String,
+    /// The full name of the parent's library, which is dropped
+    pub dropped: String,
+}
+
+/// What happened to the libraries during one or more merges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Parent libraries replaced by another version of the same library
+    pub replaced: Vec<ReplacedLibrary>,
+    /// Parent libraries dropped because the child already has the exact same library
+    pub duplicated: Vec<String>,
+}
+
+impl MergeReport {
+    /// Appends the results of another merge to this report
+    pub fn extend(&mut self, other: Self) {
+        self.replaced.extend(other.replaced);
+        self.duplicated.extend(other.duplicated);
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.replaced.is_empty() && self.duplicated.is_empty()
+    }
+}
 
-            current_arguments.jvm.extend(arguments.jvm);
-            current_arguments.game.extend(arguments.game);
+/// Identifies a library regardless of its version.
+///
+/// Natives entries (old-style libraries with a `natives` block) are kept apart from their plain
+/// counterparts, since LWJGL 2 lists the same coordinate twice.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct LibraryKey {
+    group: String,
+    artifact: String,
+    classifier: Option<String>,
+    natives: bool,
+}
+
+impl LibraryKey {
+    fn new(library: &Library) -> Self {
+        let natives = library.natives().is_some();
+
+        library.parse_name().map_or_else(
+            || Self {
+                group: library.name().to_owned(),
+                artifact: String::new(),
+                classifier: None,
+                natives,
+            },
+            |coordinate| Self {
+                group: coordinate.group().to_owned(),
+                artifact: coordinate.artifact().to_owned(),
+                classifier: coordinate.classifier().map(ToOwned::to_owned),
+                natives,
+            },
+        )
+    }
+}
 
-            self.arguments = Some(current_arguments);
-            self.minecraft_arguments = None;
+/// How a child's legacy `minecraftArguments` are combined with its parent's arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LegacyArguments {
+    /// Replace if the child's arguments are a complete argument line (legacy Forge profiles copy
+    /// the whole vanilla line), append otherwise.
+    #[default]
+    Auto,
+    /// The child's arguments replace the parent's game arguments
+    Replace,
+    /// The child's arguments are appended to the parent's game arguments
+    Append,
+}
+
+impl LegacyArguments {
+    fn appends(self, child: &str) -> bool {
+        match self {
+            Self::Auto => !child.contains("${auth_player_name}"),
+            Self::Replace => false,
+            Self::Append => true,
         }
+    }
+}
+
+/// Getter methods
+impl MergableManifest {
+    #[must_use]
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    #[must_use]
+    pub fn inherits_from(&self) -> Option<&str> {
+        self.inherits_from.as_deref()
+    }
+
+    #[must_use]
+    pub const fn asset_index(&self) -> Option<&AssetIndex> {
+        self.asset_index.as_ref()
+    }
+
+    #[must_use]
+    pub const fn downloads(&self) -> Option<&Downloads> {
+        self.downloads.as_ref()
+    }
+
+    #[must_use]
+    pub fn libraries(&self) -> &[Library] {
+        &self.libraries
+    }
+
+    #[must_use]
+    pub fn minecraft_arguments(&self) -> Option<&str> {
+        self.minecraft_arguments.as_deref()
+    }
+
+    #[must_use]
+    pub const fn arguments(&self) -> Option<&Arguments> {
+        self.arguments.as_ref()
+    }
+
+    #[must_use]
+    pub const fn logging(&self) -> Option<&Logging> {
+        self.logging.as_ref()
+    }
+}
+
+impl MergableManifest {
+    pub(crate) fn set_inherits_from(&mut self, inherits_from: Option<String>) {
+        self.inherits_from = inherits_from;
+    }
+
+    /// Saves the manifest to disk in the given format. The file is replaced atomically, and
+    /// unknown fields from the original file are kept.
+    ///
+    /// # Errors
+    /// Returns a [`SaveError`] if the manifest could not be serialized or if an IO error occurred.
+    pub async fn save_to_disk(
+        &self,
+        path: &Path,
+        format: OutputFormat,
+    ) -> error_stack::Result<(), SaveError> {
+        client::save_json(self, path, format).await
+    }
+
+    /// Merges `other` (the parent) into this manifest (the child).
+    ///
+    /// Returns a report of which of the parent's libraries were dropped because the child already
+    /// provides them. Legacy `minecraftArguments` are merged with [`LegacyArguments::Auto`].
+    pub fn merge_with(&mut self, other: Self) -> MergeReport {
+        self.merge_with_strategy(other, LegacyArguments::Auto)
+    }
+
+    /// Same as [`Self::merge_with`], but with an explicit way of merging the child's
+    /// `minecraftArguments` into the parent's.
+    pub fn merge_with_strategy(&mut self, other: Self, strategy: LegacyArguments) -> MergeReport {
+        // arguments (parent first, then the child)
+        self.merge_arguments(other.minecraft_arguments, other.arguments, strategy);
+
+        // assets (overriding)
+        self.assets = self.assets.take().or(other.assets);
 
         // inheriting (overriding reverse)
         self.inherits_from = other.inherits_from.or_else(|| self.inherits_from.take());
@@ -64,8 +261,11 @@ impl MergableManifest {
         // java version (overriding)
         self.java_version = self.java_version.take().or(other.java_version);
 
-        // library (combining)
-        self.libraries.extend(other.libraries);
+        // logging (overriding)
+        self.logging = self.logging.take().or(other.logging);
+
+        // library (combining, the child's version wins)
+        let report = self.merge_libraries(other.libraries);
 
         // main class (overriding)
         self.main_class = self.main_class.take().or(other.main_class);
@@ -81,5 +281,403 @@ impl MergableManifest {
 
         // time (overriding)
         self.time = self.time.take().or(other.time);
+
+        // type (overriding)
+        self.manifest_type = self.manifest_type.take().or(other.manifest_type);
+
+        // unknown fields (overriding)
+        for (key, value) in other.extra {
+            self.extra.entry(key).or_insert(value);
+        }
+
+        report
+    }
+
+    fn merge_arguments(
+        &mut self,
+        parent_legacy: Option<String>,
+        parent_arguments: Option<Arguments>,
+        strategy: LegacyArguments,
+    ) {
+        let child_legacy = self.minecraft_arguments.take();
+        let child_arguments = self.arguments.take();
+
+        // both sides are legacy, so the result can stay legacy
+        if parent_arguments.is_none() && child_arguments.is_none() {
+            self.minecraft_arguments = match (parent_legacy, child_legacy) {
+                (Some(parent), Some(child)) if strategy.appends(&child) => {
+                    Some(format!("{parent} {child}"))
+                }
+                (parent, child) => child.or(parent),
+            }
+            .map(|arguments| {
+                dedup_argument_pairs(arguments.split_whitespace().map(ToOwned::to_owned)).join(" ")
+            });
+
+            return;
+        }
+
+        // one side is modern, so convert the legacy side instead of dropping its game arguments
+        let mut arguments = parent_arguments
+            .or_else(|| parent_legacy.as_deref().map(Arguments::from_legacy))
+            .unwrap_or_default();
+
+        if let Some(child) = child_arguments {
+            if let Some(jvm) = child.jvm {
+                arguments.jvm.get_or_insert_with(Vec::new).extend(jvm);
+            }
+
+            if let Some(game) = child.game {
+                arguments.game.get_or_insert_with(Vec::new).extend(game);
+            }
+
+            arguments.extra.extend(child.extra);
+        } else if let Some(child) = child_legacy {
+            let game = arguments.game.get_or_insert_with(Vec::new);
+
+            if !strategy.appends(&child) {
+                game.clear();
+            }
+
+            game.extend(Arguments::from_legacy(&child).game.into_iter().flatten());
+        }
+
+        arguments.game = arguments.game.map(dedup_game_arguments);
+        self.arguments = Some(arguments);
+    }
+
+    fn merge_libraries(&mut self, parent: Vec<Library>) -> MergeReport {
+        let mut report = MergeReport::default();
+
+        let child = self
+            .libraries
+            .iter()
+            .map(|library| (LibraryKey::new(library), library.name().to_owned()))
+            .collect::<HashMap<_, _>>();
+
+        let kept = parent
+            .into_iter()
+            .filter(|library| match child.get(&LibraryKey::new(library)) {
+                Some(name) if name == library.name() => {
+                    report.duplicated.push(name.clone());
+                    false
+                }
+                Some(name) => {
+                    report.replaced.push(ReplacedLibrary {
+                        kept: name.clone(),
+                        dropped: library.name().to_owned(),
+                    });
+                    false
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        self.libraries.extend(kept);
+
+        report
+    }
+}
+
+/// Removes repeated `--flag value` pairs (e.g. `--tweakClass`), keeping the first occurrence.
+fn dedup_argument_pairs(arguments: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut deduped = Vec::new();
+    let mut arguments = arguments.into_iter().peekable();
+
+    while let Some(argument) = arguments.next() {
+        let is_pair = argument.starts_with("--")
+            && arguments
+                .peek()
+                .is_some_and(|value| !value.starts_with("--"));
+
+        if !is_pair {
+            deduped.push(argument);
+            continue;
+        }
+
+        let value = arguments.next().unwrap_or_default();
+
+        if seen.insert((argument.clone(), value.clone())) {
+            deduped.push(argument);
+            deduped.push(value);
+        }
+    }
+
+    deduped
+}
+
+/// Same as [`dedup_argument_pairs`], but only looks at unconditional arguments. Arguments with
+/// rules are always kept.
+fn dedup_game_arguments(arguments: Vec<Game>) -> Vec<Game> {
+    let mut seen = HashSet::new();
+    let mut deduped = Vec::new();
+    let mut arguments = arguments.into_iter().peekable();
+
+    while let Some(argument) = arguments.next() {
+        let Game::String(flag) = &argument else {
+            deduped.push(argument);
+            continue;
+        };
+
+        let value = match arguments.peek() {
+            Some(Game::String(value)) if flag.starts_with("--") && !value.starts_with("--") => {
+                value.clone()
+            }
+            _ => {
+                deduped.push(argument);
+                continue;
+            }
+        };
+
+        let value_argument = arguments.next();
+
+        if seen.insert((flag.clone(), value)) {
+            deduped.push(argument);
+            deduped.extend(value_argument);
+        }
+    }
+
+    deduped
+}
+
+impl From<client::Manifest> for MergableManifest {
+    fn from(manifest: client::Manifest) -> Self {
+        Self {
+            asset_index: Some(manifest.asset_index),
+            assets: Some(manifest.assets),
+            downloads: Some(manifest.downloads),
+            id: Some(manifest.id),
+            libraries: manifest.libraries,
+            main_class: Some(manifest.main_class),
+            minecraft_arguments: manifest.minecraft_arguments,
+            arguments: manifest.arguments,
+            minimum_launcher_version: Some(manifest.minimum_launcher_version),
+            java_version: manifest.java_version,
+            release_time: Some(manifest.release_time),
+            time: Some(manifest.time),
+            manifest_type: Some(manifest.manifest_type),
+            compliance_level: manifest.compliance_level,
+            logging: manifest.logging,
+            inherits_from: manifest
+                .inherits_from
+                .map(|path| path.to_string_lossy().into_owned()),
+            extra: manifest.extra,
+        }
+    }
+}
+
+impl TryFrom<MergableManifest> for client::Manifest {
+    type Error = ConversionError;
+
+    /// Converts a fully merged manifest into a launchable one.
+    ///
+    /// The `inheritsFrom` field is carried over as-is, so make sure the chain has been resolved
+    /// (see [`crate::resolver::Resolver`]) before launching.
+    fn try_from(manifest: MergableManifest) -> Result<Self, Self::Error> {
+        if manifest.arguments.is_none() && manifest.minecraft_arguments.is_none() {
+            return Err(ConversionError::MissingField("arguments"));
+        }
+
+        Ok(Self {
+            asset_index: manifest
+                .asset_index
+                .ok_or(ConversionError::MissingField("assetIndex"))?,
+            assets: manifest
+                .assets
+                .ok_or(ConversionError::MissingField("assets"))?,
+            downloads: manifest
+                .downloads
+                .ok_or(ConversionError::MissingField("downloads"))?,
+            id: manifest.id.ok_or(ConversionError::MissingField("id"))?,
+            libraries: manifest.libraries,
+            main_class: manifest
+                .main_class
+                .ok_or(ConversionError::MissingField("mainClass"))?,
+            minecraft_arguments: manifest.minecraft_arguments,
+            arguments: manifest.arguments,
+            minimum_launcher_version: manifest
+                .minimum_launcher_version
+                .ok_or(ConversionError::MissingField("minimumLauncherVersion"))?,
+            java_version: manifest.java_version,
+            release_time: manifest
+                .release_time
+                .ok_or(ConversionError::MissingField("releaseTime"))?,
+            time: manifest.time.ok_or(ConversionError::MissingField("time"))?,
+            manifest_type: manifest
+                .manifest_type
+                .ok_or(ConversionError::MissingField("type"))?,
+            logging: manifest.logging,
+            compliance_level: manifest.compliance_level,
+            inherits_from: manifest.inherits_from.map(PathBuf::from),
+            extra: manifest.extra,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(value: serde_json::Value) -> MergableManifest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn names(manifest: &MergableManifest) -> Vec<&str> {
+        manifest.libraries.iter().map(Library::name).collect()
+    }
+
+    #[test]
+    fn child_library_version_wins() {
+        let mut child = manifest(json!({
+            "libraries": [{ "name": "org.ow2.asm:asm:9.5", "downloads": {} }]
+        }));
+        let parent = manifest(json!({
+            "libraries": [
+                { "name": "org.ow2.asm:asm:9.3", "downloads": {} },
+                { "name": "com.google.guava:guava:31.1-jre", "downloads": {} }
+            ]
+        }));
+
+        let report = child.merge_with(parent);
+
+        assert_eq!(
+            names(&child),
+            ["org.ow2.asm:asm:9.5", "com.google.guava:guava:31.1-jre"]
+        );
+        assert_eq!(
+            report.replaced,
+            [ReplacedLibrary {
+                kept: "org.ow2.asm:asm:9.5".to_owned(),
+                dropped: "org.ow2.asm:asm:9.3".to_owned(),
+            }]
+        );
+        assert!(report.duplicated.is_empty());
+    }
+
+    #[test]
+    fn identical_libraries_are_reported_as_duplicates() {
+        let guava = json!({
+            "libraries": [{ "name": "com.google.guava:guava:31.1-jre", "downloads": {} }]
+        });
+        let mut child = manifest(guava.clone());
+        let parent = manifest(guava);
+
+        let report = child.merge_with(parent);
+
+        assert_eq!(names(&child), ["com.google.guava:guava:31.1-jre"]);
+        assert_eq!(report.duplicated, ["com.google.guava:guava:31.1-jre"]);
+    }
+
+    #[test]
+    fn classifiers_and_natives_stay_distinct() {
+        let mut child = manifest(json!({
+            "libraries": [
+                { "name": "org.lwjgl:lwjgl:3.3.1", "downloads": {} },
+                { "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.1", "downloads": {} }
+            ]
+        }));
+        let parent = manifest(json!({
+            "libraries": [
+                { "name": "org.lwjgl:lwjgl:3.3.1:natives-linux", "downloads": {} },
+                {
+                    "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                    "downloads": {},
+                    "natives": { "linux": "natives-linux" }
+                }
+            ]
+        }));
+
+        let report = child.merge_with(parent);
+
+        assert_eq!(child.libraries.len(), 4);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn legacy_forge_replaces_vanilla_arguments() {
+        let mut child = manifest(json!({
+            "minecraftArguments": "--username ${auth_player_name} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker"
+        }));
+        let parent = manifest(json!({
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name}"
+        }));
+
+        child.merge_with(parent);
+
+        assert_eq!(
+            child.minecraft_arguments.as_deref(),
+            Some("--username ${auth_player_name} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker")
+        );
+    }
+
+    #[test]
+    fn legacy_tweakers_are_appended_once() {
+        let mut child = manifest(json!({
+            "minecraftArguments": "--tweakClass optifine.OptiFineTweaker --tweakClass optifine.OptiFineTweaker"
+        }));
+        let parent = manifest(json!({
+            "minecraftArguments": "--username ${auth_player_name} --tweakClass optifine.OptiFineTweaker"
+        }));
+
+        child.merge_with(parent);
+
+        assert_eq!(
+            child.minecraft_arguments.as_deref(),
+            Some("--username ${auth_player_name} --tweakClass optifine.OptiFineTweaker")
+        );
+    }
+
+    #[test]
+    fn modern_child_keeps_legacy_parent_game_arguments() {
+        let mut child = manifest(json!({
+            "arguments": { "game": ["--tweakClass", "net.fabricmc.Tweaker"] }
+        }));
+        let parent = manifest(json!({
+            "minecraftArguments": "--username ${auth_player_name}"
+        }));
+
+        child.merge_with(parent);
+
+        let arguments = child.arguments.unwrap();
+        let game = arguments
+            .game()
+            .iter()
+            .map(|arg| match arg {
+                Game::String(arg) => arg.as_str(),
+                Game::GameClass(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            game,
+            [
+                "--username",
+                "${auth_player_name}",
+                "--tweakClass",
+                "net.fabricmc.Tweaker"
+            ]
+        );
+        assert_eq!(arguments.jvm().len(), client::LEGACY_JVM_ARGUMENTS.len());
+        assert!(child.minecraft_arguments.is_none());
+    }
+
+    #[test]
+    fn unknown_fields_round_trip() {
+        let original = json!({
+            "id": "fabric-loader-0.14.21-1.20.1",
+            "inheritsFrom": "1.20.1",
+            "_comment_": ["Generated by a loader installer"],
+            "libraries": [{
+                "name": "net.fabricmc:fabric-loader:0.14.21",
+                "url": "https://maven.fabricmc.net/",
+                "loaderOnly": true
+            }]
+        });
+
+        let manifest = manifest(original.clone());
+
+        assert_eq!(serde_json::to_value(&manifest).unwrap(), original);
     }
 }
@@ -0,0 +1,407 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use itertools::Itertools;
+use tracing::debug;
+use zip::ZipArchive;
+
+use crate::{
+    assets::client::{Artifact, Extract, Library},
+    platform::{Arch, OsName, Platform},
+    rules::RuleContext,
+};
+
+/// Written into the natives directory once extraction finishes. Lists the jars the directory was
+/// extracted from, so it can be reused by the next launch.
+const MARKER_FILE: &str = ".copper-natives";
+
+#[derive(Debug)]
+pub enum ExtractError {
+    IoError,
+    ZipError,
+    JoinError,
+}
+
+impl Display for ExtractError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::IoError => "io error",
+            Self::ZipError => "could not read natives jar",
+            Self::JoinError => "join error",
+        })
+    }
+}
+
+impl Error for ExtractError {}
+
+#[derive(Debug)]
+pub enum SelectionError {
+    /// The manifest has natives libraries, but none of them are for the platform
+    MissingNatives,
+}
+
+impl Display for SelectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingNatives => "Manifest has no natives for this platform",
+        })
+    }
+}
+
+impl Error for SelectionError {}
+
+/// The platform a natives classifier (e.g `natives-macos-arm64`) is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativesClassifier {
+    os: OsName,
+    arch: Arch,
+}
+
+impl NativesClassifier {
+    /// Parses a natives classifier. Classifiers without an architecture are for `x86_64`
+    #[must_use]
+    pub fn parse(classifier: &str) -> Option<Self> {
+        let classifier = classifier.strip_prefix("natives-")?;
+        let (os, arch) = classifier.split_once('-').unwrap_or((classifier, "x86_64"));
+
+        let arch = match arch {
+            "64" => Arch::X86_64,
+            "32" => Arch::X86,
+            arch => arch.parse().ok()?,
+        };
+
+        Some(Self {
+            os: os.parse().ok()?,
+            arch,
+        })
+    }
+
+    #[must_use]
+    pub fn matches(&self, platform: &Platform) -> bool {
+        self.os == platform.os() && self.arch == platform.arch()
+    }
+}
+
+/// The libraries to use on a platform.
+///
+/// Besides evaluating rules, this handles LWJGL 3.3+ style natives, which are separate libraries
+/// with the natives classifier in their name (e.g `org.lwjgl:lwjgl:3.3.1:natives-linux`). Rules
+/// only check the OS for these, so the architecture is matched here, keeping exactly one natives
+/// library per artifact.
+#[derive(Debug, Clone)]
+pub struct LibrarySelection<'a> {
+    libraries: Vec<&'a Library>,
+    /// `group:artifact:version` of the libraries which have natives, but not for the platform
+    missing_natives: Vec<String>,
+    platform: Platform,
+}
+
+impl<'a> LibrarySelection<'a> {
+    #[must_use]
+    pub fn new(libraries: &'a [Library], platform: &Platform) -> Self {
+        let context = RuleContext::for_platform(platform.clone());
+        let mut selected = Vec::new();
+        let mut with_natives = Vec::new();
+        let mut satisfied = Vec::new();
+
+        for library in libraries {
+            let natives = library.parse_name().and_then(|coordinate| {
+                let classifier = NativesClassifier::parse(coordinate.classifier()?)?;
+                let key = format!(
+                    "{}:{}:{}",
+                    coordinate.group(),
+                    coordinate.artifact(),
+                    coordinate.version()
+                );
+
+                Some((key, classifier))
+            });
+
+            let Some((key, classifier)) = natives else {
+                if library.rules_decision(&context).is_allowed() {
+                    selected.push(library);
+                }
+
+                continue;
+            };
+
+            if !with_natives.contains(&key) {
+                with_natives.push(key.clone());
+            }
+
+            let allowed = library.rules_decision(&context).is_allowed()
+                && classifier.matches(platform)
+                && !satisfied.contains(&key);
+
+            if allowed {
+                satisfied.push(key);
+                selected.push(library);
+            }
+        }
+
+        let missing_natives = with_natives
+            .into_iter()
+            .filter(|key| !satisfied.contains(key))
+            .collect();
+
+        Self {
+            libraries: selected,
+            missing_natives,
+            platform: platform.clone(),
+        }
+    }
+
+    /// The selected libraries, even if some natives are missing
+    #[must_use]
+    pub fn libraries(&self) -> &[&'a Library] {
+        &self.libraries
+    }
+
+    #[must_use]
+    pub fn missing_natives(&self) -> &[String] {
+        &self.missing_natives
+    }
+
+    /// Gets the selected libraries, erroring if any natives are missing for the platform
+    ///
+    /// # Errors
+    /// Errors if the manifest has natives libraries for an artifact, but none for the platform
+    pub fn into_result(self) -> Result<Vec<&'a Library>, SelectionError> {
+        if self.missing_natives.is_empty() {
+            return Ok(self.libraries);
+        }
+
+        Err(Report::new(SelectionError::MissingNatives)).attach_printable(format!(
+            "No {} {} natives for {}",
+            self.platform.os(),
+            self.platform.arch(),
+            self.missing_natives.iter().join(", ")
+        ))
+    }
+}
+
+/// Extracts the natives (LWJGL 2 style classifier jars) of the given libraries into a directory.
+///
+/// If the directory was already extracted from the same jars it is reused as-is. Otherwise it is
+/// wiped and extracted again, so natives from other versions never leak in.
+#[derive(Debug, Clone)]
+pub struct NativesExtractor {
+    libraries_directory: PathBuf,
+    natives_directory: PathBuf,
+    platform: Platform,
+}
+
+impl NativesExtractor {
+    #[must_use]
+    pub const fn new(
+        libraries_directory: PathBuf,
+        natives_directory: PathBuf,
+        platform: Platform,
+    ) -> Self {
+        Self {
+            libraries_directory,
+            natives_directory,
+            platform,
+        }
+    }
+
+    /// Extracts the natives of the given libraries, skipping any libraries not selected for the
+    /// platform
+    ///
+    /// # Errors
+    /// Errors if a natives jar is missing or invalid, or if the natives directory cannot be
+    /// written to
+    #[tracing::instrument(skip(self, libraries))]
+    pub async fn extract(&self, libraries: &[Library]) -> Result<(), ExtractError> {
+        let natives = LibrarySelection::new(libraries, &self.platform)
+            .libraries()
+            .iter()
+            .filter_map(|library| {
+                library
+                    .native_artifact(&self.platform)
+                    .map(|artifact| (artifact, library.extract().cloned()))
+            })
+            .collect::<Vec<_>>();
+
+        let extractor = self.clone();
+
+        tokio::task::spawn_blocking(move || extractor.extract_blocking(&natives))
+            .await
+            .into_report()
+            .change_context(ExtractError::JoinError)?
+    }
+
+    fn extract_blocking(
+        &self,
+        natives: &[(Artifact, Option<Extract>)],
+    ) -> Result<(), ExtractError> {
+        let marker = natives
+            .iter()
+            .map(|(artifact, _)| format!("{} {}", artifact.path(), artifact.sha1()))
+            .join("\n");
+
+        let marker_path = self.natives_directory.join(MARKER_FILE);
+
+        if fs::read_to_string(&marker_path).is_ok_and(|existing| existing == marker) {
+            debug!("Reusing natives in {}", self.natives_directory.display());
+            return Ok(());
+        }
+
+        if self
+            .natives_directory
+            .try_exists()
+            .into_report()
+            .change_context(ExtractError::IoError)?
+        {
+            debug!(
+                "Cleaning stale natives in {}",
+                self.natives_directory.display()
+            );
+            fs::remove_dir_all(&self.natives_directory)
+                .into_report()
+                .change_context(ExtractError::IoError)?;
+        }
+
+        fs::create_dir_all(&self.natives_directory)
+            .into_report()
+            .change_context(ExtractError::IoError)?;
+
+        for (artifact, extract) in natives {
+            let path = self.libraries_directory.join(artifact.path());
+            self.extract_jar(&path, extract.as_ref())
+                .attach_printable_lazy(|| format!("While extracting {}", path.display()))?;
+        }
+
+        // written last, so an interrupted extraction is never reused
+        fs::write(marker_path, marker)
+            .into_report()
+            .change_context(ExtractError::IoError)
+    }
+
+    fn extract_jar(&self, jar: &Path, extract: Option<&Extract>) -> Result<(), ExtractError> {
+        debug!("Extracting natives from {}", jar.display());
+
+        let file = File::open(jar)
+            .into_report()
+            .change_context(ExtractError::IoError)?;
+
+        let mut archive = ZipArchive::new(file)
+            .into_report()
+            .change_context(ExtractError::ZipError)?;
+
+        for index in 0..archive.len() {
+            let mut entry = archive
+                .by_index(index)
+                .into_report()
+                .change_context(ExtractError::ZipError)?;
+
+            // protects against entries like `../../evil.so`
+            let Some(name) = entry.enclosed_name().map(Path::to_path_buf) else {
+                continue;
+            };
+
+            let excluded = extract.is_some_and(|extract| extract.is_excluded(entry.name()));
+
+            if entry.is_dir() || excluded {
+                continue;
+            }
+
+            let destination = self.natives_directory.join(name);
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)
+                    .into_report()
+                    .change_context(ExtractError::IoError)?;
+            }
+
+            let mut output = File::create(&destination)
+                .into_report()
+                .change_context(ExtractError::IoError)?;
+
+            io::copy(&mut entry, &mut output)
+                .into_report()
+                .change_context(ExtractError::IoError)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test_case("natives-linux", OsName::Linux, Arch::X86_64)]
+    #[test_case("natives-linux-arm64", OsName::Linux, Arch::Arm64)]
+    #[test_case("natives-macos-arm64", OsName::Osx, Arch::Arm64)]
+    #[test_case("natives-windows-x86", OsName::Windows, Arch::X86)]
+    #[test_case("natives-windows-64", OsName::Windows, Arch::X86_64)]
+    fn parse_classifier(classifier: &str, os: OsName, arch: Arch) {
+        assert_eq!(
+            NativesClassifier::parse(classifier),
+            Some(NativesClassifier { os, arch })
+        );
+    }
+
+    fn libraries() -> Vec<Library> {
+        let library = |classifier: &str, os: &str| {
+            json!({
+                "name": format!("org.lwjgl:lwjgl:3.3.1:{classifier}"),
+                "downloads": {},
+                "rules": [{ "action": "allow", "os": { "name": os } }]
+            })
+        };
+
+        serde_json::from_value(json!([
+            { "name": "org.lwjgl:lwjgl:3.3.1", "downloads": {} },
+            library("natives-linux", "linux"),
+            library("natives-macos", "osx"),
+            library("natives-macos-arm64", "osx"),
+            library("natives-windows", "windows"),
+            library("natives-windows-x86", "windows"),
+        ]))
+        .unwrap()
+    }
+
+    #[test_case(OsName::Linux, Arch::X86_64, "natives-linux")]
+    #[test_case(OsName::Osx, Arch::Arm64, "natives-macos-arm64")]
+    #[test_case(OsName::Osx, Arch::X86_64, "natives-macos")]
+    #[test_case(OsName::Windows, Arch::X86, "natives-windows-x86")]
+    fn selects_one_natives_library(os: OsName, arch: Arch, classifier: &str) {
+        let libraries = libraries();
+        let selected = LibrarySelection::new(&libraries, &Platform::new(os, arch, None))
+            .into_result()
+            .unwrap();
+
+        let names = selected
+            .iter()
+            .map(|library| library.name())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            [
+                "org.lwjgl:lwjgl:3.3.1".to_owned(),
+                format!("org.lwjgl:lwjgl:3.3.1:{classifier}")
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_natives() {
+        let libraries = libraries();
+        let selection =
+            LibrarySelection::new(&libraries, &Platform::new(OsName::Linux, Arch::Arm64, None));
+
+        assert_eq!(selection.missing_natives(), ["org.lwjgl:lwjgl:3.3.1"]);
+        assert!(selection.into_result().is_err());
+    }
+}
@@ -1,270 +1,766 @@
-use std::iter;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt, iter,
+    path::Path,
+    slice,
+    sync::OnceLock,
+};
 
 use itertools::Itertools;
+use regex::{Captures, Regex};
 use tracing::debug;
 
 use crate::{
-    assets::client::{self, Args, Artifact, Classifiers, Library, Rule},
+    assets::{
+        asset_index::AssetLayout,
+        client::{self, Args, Arguments, Library},
+    },
     launcher::{Launcher, Quickplay},
+    log4j::Log4jMitigation,
+    natives::LibrarySelection,
+    rules::{self, Condition, FeatureSet, RuleContext},
 };
 
-pub struct JvmArgs<'a> {
-    launcher: &'a Launcher,
-    manifest: &'a client::Manifest,
+/// Every placeholder vanilla manifests use in their arguments, e.g `${auth_player_name}`
+pub const PLACEHOLDERS: &[&str] = &[
+    "auth_player_name",
+    "version_name",
+    "game_directory",
+    "assets_root",
+    "game_assets",
+    "assets_index_name",
+    "auth_uuid",
+    "auth_access_token",
+    "auth_session",
+    "auth_xuid",
+    "clientid",
+    "user_type",
+    "user_properties",
+    "version_type",
+    "resolution_width",
+    "resolution_height",
+    "quickPlayPath",
+    "quickPlaySingleplayer",
+    "quickPlayMultiplayer",
+    "quickPlayRealms",
+    "natives_directory",
+    "launcher_name",
+    "launcher_version",
+    "classpath",
+    // used by Forge
+    "library_directory",
+    "classpath_separator",
+];
+
+/// What sensitive values are replaced with in `Debug` output and rendered commands
+pub const REDACTED: &str = "<redacted>";
+
+/// A single launch argument.
+///
+/// Arguments built from sensitive placeholders (e.g the access token) are masked in their `Debug`
+/// output, so they can be logged safely. The real value is still what gets passed to the game.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Argument {
+    value: String,
+    /// The value with sensitive parts replaced by [`REDACTED`]
+    redacted: Option<String>,
 }
 
-impl<'a> JvmArgs<'a> {
+impl Argument {
     #[must_use]
-    pub const fn new(launcher: &'a Launcher, manifest: &'a client::Manifest) -> Self {
-        Self { launcher, manifest }
+    pub const fn new(value: String) -> Self {
+        Self {
+            value,
+            redacted: None,
+        }
     }
 
     #[must_use]
-    pub fn parse_jvm_args(&self) -> Vec<String> {
-        let Args::Arguments(args) = self.manifest.get_arguments() else {
-            return Vec::new();
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Gets the value with sensitive parts masked, safe to show to users
+    #[must_use]
+    pub fn redacted(&self) -> &str {
+        self.redacted.as_deref().unwrap_or(&self.value)
+    }
+
+    #[must_use]
+    pub const fn is_sensitive(&self) -> bool {
+        self.redacted.is_some()
+    }
+
+    #[must_use]
+    pub fn into_value(self) -> String {
+        self.value
+    }
+}
+
+impl fmt::Debug for Argument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.redacted(), f)
+    }
+}
+
+impl From<String> for Argument {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Argument {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_owned())
+    }
+}
+
+impl AsRef<str> for Argument {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl AsRef<OsStr> for Argument {
+    fn as_ref(&self) -> &OsStr {
+        self.value.as_ref()
+    }
+}
+
+impl PartialEq<&str> for Argument {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+/// The values substituted for `${placeholder}`s in arguments
+#[derive(Clone, Default)]
+pub struct Placeholders {
+    values: HashMap<String, String>,
+    sensitive: HashSet<String>,
+}
+
+impl Placeholders {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the placeholders for launching the game, covering every placeholder in
+    /// [`PLACEHOLDERS`]
+    #[must_use]
+    pub fn for_launch(
+        launcher: &Launcher,
+        manifest: &client::Manifest,
+        asset_layout: AssetLayout,
+    ) -> Self {
+        let mut placeholders = Self::new();
+        let auth = launcher.authentication_details();
+        let uuid = auth.minecraft_profile.id();
+        let access_token = &auth.auth_details.access_token;
+        let path = |path: &Path| path.to_str().unwrap_or_default().to_owned();
+
+        // auth
+        placeholders.insert("auth_player_name", &auth.auth_details.username);
+        placeholders.insert("auth_uuid", uuid);
+        placeholders.insert_sensitive("auth_access_token", access_token);
+        // only used by versions before 1.6
+        placeholders.insert_sensitive("auth_session", format!("token:{access_token}:{uuid}"));
+        placeholders.insert_sensitive("auth_xuid", launcher.xuid().unwrap_or_default());
+        placeholders.insert_sensitive("clientid", launcher.client_id().unwrap_or_default());
+        placeholders.insert("user_type", "msa"); // copper only supports MSA
+        placeholders.insert_sensitive("user_properties", "{}");
+
+        // version
+        let version_name = launcher.version_name().replace([' ', ':'], "_");
+        placeholders.insert("version_name", &version_name);
+        placeholders.insert("assets_index_name", manifest.asset_index().id());
+        placeholders.insert(
+            "version_type",
+            if launcher.is_snapshot() {
+                "snapshot"
+            } else {
+                "release"
+            },
+        );
+
+        // directories
+        placeholders.insert("game_directory", path(launcher.game_directory()));
+        placeholders.insert("assets_root", path(launcher.assets_directory()));
+        placeholders.insert(
+            "game_assets",
+            path(&launcher.game_assets_directory(asset_layout)),
+        );
+        placeholders.insert("natives_directory", path(launcher.natives_directory()));
+        placeholders.insert("library_directory", path(launcher.libraries_directory()));
+
+        // window
+        let resolution = launcher.custom_resolution();
+        placeholders.insert(
+            "resolution_width",
+            resolution.map(|r| r.width.to_string()).unwrap_or_default(),
+        );
+        placeholders.insert(
+            "resolution_height",
+            resolution.map(|r| r.height.to_string()).unwrap_or_default(),
+        );
+
+        // quick play
+        let quickplay = |filter: fn(&Quickplay) -> bool| {
+            launcher
+                .quickplay()
+                .filter(|quickplay| filter(quickplay))
+                .map(Quickplay::value)
+                .unwrap_or_default()
         };
+        placeholders.insert("quickPlayPath", path(&launcher.quickplay_path()));
+        placeholders.insert(
+            "quickPlaySingleplayer",
+            quickplay(Quickplay::is_singleplayer),
+        );
+        placeholders.insert("quickPlayMultiplayer", quickplay(Quickplay::is_multiplayer));
+        placeholders.insert("quickPlayRealms", quickplay(Quickplay::is_realms));
+
+        // launcher
+        placeholders.insert("launcher_name", launcher.launcher_name());
+        placeholders.insert("launcher_version", launcher.launcher_version());
+        placeholders.insert("classpath", classpath(launcher, manifest));
+        placeholders.insert(
+            "classpath_separator",
+            launcher.platform().classpath_separator(),
+        );
+
+        placeholders
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+
+        self.sensitive.remove(&name);
+        self.values.insert(name, value.into());
+    }
+
+    /// Inserts a value that is masked wherever it is printed, see [`Argument`]
+    pub fn insert_sensitive(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+
+        self.values.insert(name.clone(), value.into());
+        self.sensitive.insert(name);
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Replaces every known placeholder in the argument. Unknown placeholders are left as is, see
+    /// [`unresolved_placeholders`]. Values are never substituted again, so a username containing
+    /// `${...}` stays as is.
+    #[must_use]
+    pub fn substitute(&self, argument: &str) -> String {
+        placeholder_regex()
+            .replace_all(argument, |captures: &Captures| {
+                self.get(&captures[1]).unwrap_or(&captures[0]).to_owned()
+            })
+            .into_owned()
+    }
+
+    /// Substitutes the argument like [`Placeholders::substitute`], keeping track of sensitive
+    /// values so they can be masked
+    #[must_use]
+    pub fn resolve(&self, argument: &str) -> Argument {
+        let value = self.substitute(argument);
+
+        let is_sensitive = placeholder_regex()
+            .captures_iter(argument)
+            .any(|captures| self.sensitive.contains(&captures[1]));
 
-        let jvm = args.jvm();
-        jvm.iter()
-            .map(|arg| match arg {
-                client::Jvm::String(arg) => self.parse_java_arg_str(arg),
-                client::Jvm::Class(class) => {
-                    let passes = class.rules().iter().all(Rule::passes);
-
-                    if !passes {
-                        return String::new();
-                    };
-
-                    match class.value() {
-                        client::Value::String(s) => self.parse_java_arg_str(s),
-                        client::Value::StringArray(a) => {
-                            a.iter().map(|v| self.parse_java_arg_str(v)).join(" ")
-                        }
-                    }
+        if !is_sensitive {
+            return Argument::new(value);
+        }
+
+        let redacted = placeholder_regex()
+            .replace_all(argument, |captures: &Captures| {
+                if self.sensitive.contains(&captures[1]) {
+                    REDACTED
+                } else {
+                    self.get(&captures[1]).unwrap_or(&captures[0])
                 }
+                .to_owned()
             })
-            .filter(|s| !s.is_empty())
-            .collect()
+            .into_owned();
+
+        Argument {
+            value,
+            redacted: Some(redacted),
+        }
     }
+}
 
-    fn parse_java_arg_str(&self, arg: &str) -> String {
-        arg.replace(
-            "${natives_directory}",
-            self.launcher
-                .libraries_directory()
-                .to_str()
-                .unwrap_or_default(),
-        )
-        .replace("${launcher_name}", self.launcher.launcher_name())
-        .replace("${launcher_version}", self.launcher.launcher_version())
-        .replace("${classpath}", &self.get_classpath())
+impl fmt::Debug for Placeholders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.values.iter().map(|(name, value)| {
+            if self.sensitive.contains(name) {
+                (name, REDACTED)
+            } else {
+                (name, value.as_str())
+            }
+        });
+
+        f.debug_map()
+            .entries(values.sorted_by_key(|(name, _)| *name))
+            .finish()
     }
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+
+    REGEX.get_or_init(|| Regex::new(r"\$\{([^}]*)\}").expect("placeholder pattern is valid"))
+}
+
+/// Lists the placeholders (without `${}`) still in the arguments after substitution
+#[must_use]
+pub fn unresolved_placeholders<S: AsRef<str>>(arguments: &[S]) -> Vec<String> {
+    arguments
+        .iter()
+        .flat_map(|argument| placeholder_regex().captures_iter(argument.as_ref()))
+        .map(|captures| captures[1].to_owned())
+        .unique()
+        .collect()
+}
 
-    fn get_classpath(&self) -> String {
-        let libraries = self.manifest.libraries();
+/// Resolves the game arguments of a manifest into an argv. Legacy `minecraftArguments` strings are
+/// split before substitution, so values containing spaces stay a single argument
+#[must_use]
+pub fn game_arguments(
+    args: &Args,
+    placeholders: &Placeholders,
+    context: &RuleContext,
+) -> Vec<Argument> {
+    arguments(args)
+        .game()
+        .iter()
+        .flat_map(|arg| match arg {
+            client::Game::String(arg) => slice::from_ref(arg),
+            client::Game::GameClass(class) => allowed_values(class.rules(), class.value(), context),
+        })
+        .map(|arg| placeholders.resolve(arg))
+        .collect()
+}
 
-        libraries
-            .iter()
-            .filter(|lib| lib.check_rules_passes())
-            .flat_map(|lib| self.get_lib_path(lib))
-            .chain(iter::once(
-                self.launcher.jar_path().to_str().unwrap().to_string(),
-            ))
-            .join(if cfg!(windows) { ";" } else { ":" })
+/// Resolves the JVM arguments of a manifest into an argv, using [`client::LEGACY_JVM_ARGUMENTS`]
+/// for legacy manifests
+#[must_use]
+pub fn jvm_arguments(
+    args: &Args,
+    placeholders: &Placeholders,
+    context: &RuleContext,
+) -> Vec<Argument> {
+    arguments(args)
+        .jvm()
+        .iter()
+        .flat_map(|arg| match arg {
+            client::Jvm::String(arg) => slice::from_ref(arg),
+            client::Jvm::Class(class) => allowed_values(class.rules(), class.value(), context),
+        })
+        .map(|arg| placeholders.resolve(arg))
+        .collect()
+}
+
+fn arguments<'a>(args: &Args<'a>) -> Cow<'a, Arguments> {
+    match args {
+        Args::MinecraftArguments(minecraft_args) => {
+            Cow::Owned(Arguments::from_legacy(minecraft_args))
+        }
+        Args::Arguments(args) => Cow::Borrowed(*args),
     }
+}
 
-    fn get_lib_path(&self, lib: &Library) -> Vec<String> {
-        let download = lib.downloads();
+/// Gets the values of a conditional argument, or nothing if its rules do not allow it
+fn allowed_values<'a, C: Condition + 'a>(
+    rules: &'a [C],
+    value: &'a client::Value,
+    context: &RuleContext,
+) -> &'a [String] {
+    if !rules::evaluate(rules, context).is_allowed() {
+        return &[];
+    }
 
-        let paths = [
-            download.classifiers().and_then(Classifiers::current_os),
-            download.artifact(),
-        ];
+    match value {
+        client::Value::String(value) => slice::from_ref(value),
+        client::Value::StringArray(values) => values,
+    }
+}
+
+fn classpath(launcher: &Launcher, manifest: &client::Manifest) -> String {
+    let libraries = LibrarySelection::new(manifest.libraries(), launcher.platform());
+
+    libraries
+        .libraries()
+        .iter()
+        .flat_map(|lib| lib_paths(launcher, lib))
+        .chain(iter::once(
+            launcher.jar_path().to_str().unwrap().to_string(),
+        ))
+        .join(launcher.platform().classpath_separator())
+}
+
+fn lib_paths(launcher: &Launcher, lib: &Library) -> Vec<String> {
+    let paths = [lib.native_artifact(launcher.platform()), lib.artifact()];
+
+    paths
+        .into_iter()
+        .flatten()
+        .map(|artifact| launcher.libraries_directory().join(artifact.path()))
+        .filter_map(|path| dunce::canonicalize(path).ok())
+        .filter_map(|path| path.to_str().map(ToString::to_string))
+        .collect()
+}
+
+pub struct JvmArgs<'a> {
+    launcher: &'a Launcher,
+    manifest: &'a client::Manifest,
+    placeholders: &'a Placeholders,
+}
+
+impl<'a> JvmArgs<'a> {
+    #[must_use]
+    pub const fn new(
+        launcher: &'a Launcher,
+        manifest: &'a client::Manifest,
+        placeholders: &'a Placeholders,
+    ) -> Self {
+        Self {
+            launcher,
+            manifest,
+            placeholders,
+        }
+    }
 
-        paths
+    #[must_use]
+    pub fn parse_jvm_args(&self) -> Vec<Argument> {
+        let mut args = jvm_arguments(
+            &self.manifest.get_arguments(),
+            self.placeholders,
+            &self.rule_context(),
+        );
+        args.extend(self.logging_arguments().into_iter().map(Argument::from));
+
+        args
+    }
+
+    /// The arguments pointing log4j to the downloaded logging config, if the manifest has one,
+    /// and mitigating CVE-2021-44228
+    fn logging_arguments(&self) -> Vec<String> {
+        let mitigation = self.launcher.log4j_mitigation();
+
+        let logging = match mitigation {
+            // the patched config replaces the manifest's one
+            Some(Log4jMitigation::PatchedConfig(_)) => None,
+            _ => self.logging_argument(),
+        };
+
+        logging
             .into_iter()
-            .flatten()
-            .map(Artifact::path)
-            .map(|path| self.launcher.libraries_directory().join(path))
-            .filter_map(|path| dunce::canonicalize(path).ok())
-            .filter_map(|path| path.to_str().map(ToString::to_string))
+            .chain(
+                mitigation.into_iter().flat_map(|mitigation| {
+                    mitigation.jvm_arguments(self.launcher.assets_directory())
+                }),
+            )
             .collect()
     }
+
+    fn logging_argument(&self) -> Option<String> {
+        let client = self.manifest.logging()?.client();
+        let config = client.file().path(self.launcher.assets_directory());
+
+        Some(
+            client
+                .argument()
+                .replace("${path}", config.to_str().unwrap_or_default()),
+        )
+    }
+
+    fn rule_context(&self) -> RuleContext {
+        RuleContext::for_platform(self.launcher.platform().clone())
+    }
 }
 
 pub struct MinecraftArgs<'a> {
     launcher: &'a Launcher,
     manifest: &'a client::Manifest,
+    placeholders: &'a Placeholders,
 }
 
 impl<'a> MinecraftArgs<'a> {
     #[must_use]
-    pub const fn new(launcher: &'a Launcher, manifest: &'a client::Manifest) -> Self {
-        Self { launcher, manifest }
+    pub const fn new(
+        launcher: &'a Launcher,
+        manifest: &'a client::Manifest,
+        placeholders: &'a Placeholders,
+    ) -> Self {
+        Self {
+            launcher,
+            manifest,
+            placeholders,
+        }
     }
 
     #[must_use]
     #[tracing::instrument(skip(self))]
-    pub fn parse_minecraft_args(&self) -> Vec<String> {
+    pub fn parse_minecraft_args(&self) -> Vec<Argument> {
         debug!("Parsing minecraft args");
         let args = self.manifest.get_arguments();
-
-        match args {
-            client::Args::MinecraftArguments(minecraft_args) => {
-                debug!("Minecraft args: {}", minecraft_args);
-                vec![self.parse_minecraft_arg_str(minecraft_args)]
+        let context = RuleContext::new(self.launcher.platform().clone(), self.features());
+        let mut game_args = game_arguments(&args, self.placeholders, &context);
+
+        if let Some(Quickplay::Multiplayer(address)) = self.launcher.quickplay() {
+            if !self.supports_quickplay() {
+                debug!("Version has no quickplay support, using --server instead");
+                game_args.extend(
+                    legacy_server_arguments(address)
+                        .into_iter()
+                        .map(Argument::from),
+                );
             }
-            client::Args::Arguments(args) => {
-                debug!("Arguments: {:?}", args);
-                let game_args = args.game();
+        }
+
+        game_args
+    }
 
-                game_args
+    /// Whether the manifest has quickplay arguments. Versions before 1.20 only know `--server`
+    fn supports_quickplay(&self) -> bool {
+        let Args::Arguments(args) = self.manifest.get_arguments() else {
+            return false;
+        };
+
+        args.game().iter().any(|arg| match arg {
+            client::Game::String(arg) => arg.contains("${quickPlayMultiplayer}"),
+            client::Game::GameClass(class) => match class.value() {
+                client::Value::String(arg) => arg.contains("${quickPlayMultiplayer}"),
+                client::Value::StringArray(args) => args
                     .iter()
-                    .map(|arg| match arg {
-                        client::Game::GameClass(class) => {
-                            let passes = class
-                                .rules()
-                                .iter()
-                                .all(|rule| self.minecraft_rule_passes(rule));
-
-                            if !passes {
-                                return String::new();
-                            };
-
-                            match class.value() {
-                                client::Value::String(s) => self.parse_minecraft_arg_str(s),
-                                client::Value::StringArray(a) => {
-                                    a.iter().map(|v| self.parse_minecraft_arg_str(v)).join(" ")
-                                }
-                            }
-                        }
-                        client::Game::String(arg) => self.parse_minecraft_arg_str(arg),
-                    })
-                    .filter(|s| !s.is_empty())
-                    .collect()
-            }
+                    .any(|arg| arg.contains("${quickPlayMultiplayer}")),
+            },
+        })
+    }
+
+    /// Gets the features enabled for this launch, used to evaluate game argument rules
+    #[must_use]
+    pub fn features(&self) -> FeatureSet {
+        let quickplay = self.launcher.quickplay();
+
+        FeatureSet {
+            is_demo_user: self.launcher.authentication_details().is_demo_user,
+            has_custom_resolution: self.launcher.custom_resolution().is_some(),
+            has_quick_plays_support: quickplay.is_some(),
+            is_quick_play_singleplayer: quickplay.is_some_and(Quickplay::is_singleplayer),
+            is_quick_play_multiplayer: quickplay.is_some_and(Quickplay::is_multiplayer),
+            is_quick_play_realms: quickplay.is_some_and(Quickplay::is_realms),
         }
     }
+}
 
-    #[tracing::instrument(skip(self))]
-    fn parse_minecraft_arg_str(&self, minecraft_arg: &str) -> String {
-        debug!("Parsing minecraft arg: {}", minecraft_arg);
+/// Gets the `--server`/`--port` arguments for a server address like `example.com`,
+/// `example.com:25566` or `[::1]:25566`
+fn legacy_server_arguments(address: &str) -> Vec<String> {
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() && !host.ends_with(':') => (host, port),
+        _ => (address, "25565"),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    vec![
+        "--server".to_owned(),
+        host.to_owned(),
+        "--port".to_owned(),
+        port.to_owned(),
+    ]
+}
 
-        minecraft_arg
-            .replace(
-                "${auth_player_name}",
-                &self.launcher.authentication_details().auth_details.username,
-            )
-            .replace(
-                "${version_name}",
-                &self.launcher.version_name().replace([' ', ':'], "_"),
-            )
-            .replace(
-                "${game_directory}",
-                self.launcher.game_directory().to_str().unwrap_or_default(),
-            )
-            .replace(
-                "${assets_root}",
-                self.launcher
-                    .assets_directory()
-                    .to_str()
-                    .unwrap_or_default(),
-            )
-            .replace(
-                "${assets_index_name}",
-                &self.launcher.version_name().replace([' ', ':'], "_"),
-            )
-            .replace(
-                "${auth_uuid}",
-                self.launcher
-                    .authentication_details()
-                    .minecraft_profile
-                    .id(),
-            )
-            .replace(
-                "${auth_access_token}",
-                &self
-                    .launcher
-                    .authentication_details()
-                    .auth_details
-                    .access_token,
-            )
-            .replace("${user_type}", "msa") // copper only supports MSA
-            .replace(
-                "${version_type}",
-                if self.launcher.is_snapshot() {
-                    "snapshot"
-                } else {
-                    "release"
-                },
-            )
-            .replace(
-                "${resolution_width}",
-                &self
-                    .launcher
-                    .custom_resolution()
-                    .map(|r| r.width.to_string())
-                    .unwrap_or_default(),
-            )
-            .replace(
-                "${resolution_height}",
-                &self
-                    .launcher
-                    .custom_resolution()
-                    .as_ref()
-                    .map(|r| r.height.to_string())
-                    .unwrap_or_default(),
-            )
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::platform::{Arch, OsName, Platform};
+
+    fn placeholders() -> Placeholders {
+        let mut placeholders = Placeholders::new();
+        placeholders.insert("auth_player_name", "Steve");
+        placeholders.insert("auth_session", "token:abc:1234");
+        placeholders.insert("version_name", "1.12.2");
+        placeholders.insert("game_directory", "/home/steve/My Games/.minecraft");
+        placeholders.insert("assets_root", "/home/steve/My Games/.minecraft/assets");
+        placeholders.insert("assets_index_name", "1.12");
+        placeholders.insert("auth_uuid", "1234");
+        placeholders.insert("auth_access_token", "abc");
+        placeholders.insert("user_properties", "{}");
+        placeholders.insert("user_type", "msa");
+        placeholders.insert("version_type", "release");
+        placeholders.insert("natives_directory", "/natives dir");
+        placeholders.insert("classpath", "a.jar:b.jar");
+        placeholders.insert("launcher_name", "copper");
+
+        placeholders
     }
 
-    fn quickplay_check<T: Fn(&Quickplay) -> bool>(&self, x: bool, qp: T) -> bool {
-        x == self.launcher.quickplay().map(qp).unwrap_or_default()
+    fn linux() -> RuleContext {
+        RuleContext::for_platform(Platform::new(OsName::Linux, Arch::X86_64, None))
     }
 
-    fn minecraft_rule_passes(&self, rule: &client::GameRule) -> bool {
-        match rule.action() {
-            client::Action::Allow => {
-                let features = rule.features();
+    #[test]
+    fn substitute() {
+        let mut placeholders = Placeholders::new();
+        placeholders.insert("auth_player_name", "${auth_uuid}");
+        placeholders.insert("auth_uuid", "1234");
+
+        assert_eq!(
+            placeholders.substitute("--username ${auth_player_name} ${auth_uuid}"),
+            "--username ${auth_uuid} 1234"
+        );
+        assert_eq!(placeholders.substitute("${unknown}"), "${unknown}");
+    }
 
-                let demo_check = features.demo_user().map_or(true, |demo_user| {
-                    demo_user == self.launcher.authentication_details().is_demo_user
-                });
+    #[test]
+    fn sensitive() {
+        let mut placeholders = Placeholders::new();
+        placeholders.insert("auth_uuid", "1234");
+        placeholders.insert_sensitive("auth_access_token", "abc");
 
-                let support_check =
-                    features
-                        .quick_plays_support()
-                        .map_or(true, |quick_plays_support| {
-                            quick_plays_support == self.launcher.quickplay().is_some()
-                        });
+        let argument = placeholders.resolve("token:${auth_access_token}:${auth_uuid}");
 
-                let quickplay_singleplayer_check =
-                    features.quick_play_singleplayer().map_or(true, |x| {
-                        self.quickplay_check(x, Quickplay::is_singleplayer)
-                    });
+        assert_eq!(argument.value(), "token:abc:1234");
+        assert_eq!(format!("{argument:?}"), r#""token:<redacted>:1234""#);
+        assert!(!format!("{placeholders:?}").contains("abc"));
+    }
 
-                let quickplay_multiplayer_check = features
-                    .quick_play_multiplayer()
-                    .map_or(true, |x| self.quickplay_check(x, Quickplay::is_multiplayer));
+    #[test]
+    fn unresolved() {
+        let arguments = vec![
+            "--clientId".to_owned(),
+            "${clientid}".to_owned(),
+            "${foo}-${clientid}".to_owned(),
+        ];
 
-                let quickplay_realms_check = features
-                    .quick_play_realms()
-                    .map_or(true, |x| self.quickplay_check(x, Quickplay::is_realms));
+        assert_eq!(unresolved_placeholders(&arguments), ["clientid", "foo"]);
+    }
 
-                let custom_resolution_check = features
-                    .custom_resolution()
-                    .map_or(true, |x| x == self.launcher.custom_resolution().is_some());
+    #[test_case("example.com", "example.com", "25565" ; "default port")]
+    #[test_case("example.com:25566", "example.com", "25566" ; "with port")]
+    #[test_case("[::1]:25566", "::1", "25566" ; "ipv6")]
+    fn legacy_server(address: &str, host: &str, port: &str) {
+        assert_eq!(
+            legacy_server_arguments(address),
+            ["--server", host, "--port", port]
+        );
+    }
 
-                demo_check
-                    && support_check
-                    && quickplay_singleplayer_check
-                    && quickplay_multiplayer_check
-                    && quickplay_realms_check
-                    && custom_resolution_check
-            }
-            client::Action::Disallow => {
-                todo!("disallow rules are not supported yet, as none exist")
-            }
-        }
+    #[test]
+    fn legacy_1_7_10() {
+        let args = Args::MinecraftArguments(
+            "--username ${auth_player_name} --session ${auth_session} --gameDir ${game_directory} --userProperties ${user_properties}",
+        );
+
+        assert_eq!(
+            game_arguments(&args, &placeholders(), &linux()),
+            [
+                "--username",
+                "Steve",
+                "--session",
+                "token:abc:1234",
+                "--gameDir",
+                "/home/steve/My Games/.minecraft",
+                "--userProperties",
+                "{}"
+            ]
+        );
+        assert_eq!(
+            jvm_arguments(&args, &placeholders(), &linux()),
+            ["-Djava.library.path=/natives dir", "-cp", "a.jar:b.jar"]
+        );
+    }
+
+    #[test]
+    fn legacy_1_12_2() {
+        let args = Args::MinecraftArguments(
+            "--username ${auth_player_name} --version ${version_name} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --userType ${user_type} --versionType ${version_type}",
+        );
+
+        assert_eq!(
+            game_arguments(&args, &placeholders(), &linux()),
+            [
+                "--username",
+                "Steve",
+                "--version",
+                "1.12.2",
+                "--assetsDir",
+                "/home/steve/My Games/.minecraft/assets",
+                "--assetIndex",
+                "1.12",
+                "--userType",
+                "msa",
+                "--versionType",
+                "release"
+            ]
+        );
+    }
+
+    #[test]
+    fn modern() {
+        let arguments: Arguments = serde_json::from_value(json!({
+            "game": [
+                "--username",
+                "${auth_player_name}",
+                {
+                    "rules": [{ "action": "allow", "features": { "is_demo_user": true } }],
+                    "value": "--demo"
+                },
+                {
+                    "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+                    "value": ["--width", "${resolution_width}"]
+                }
+            ],
+            "jvm": [
+                {
+                    "rules": [{ "action": "allow", "os": { "name": "linux" } }],
+                    "value": ["-Dos.name=Linux Mint", "-Xss1M"]
+                },
+                {
+                    "rules": [{ "action": "allow", "os": { "name": "windows" } }],
+                    "value": "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump"
+                },
+                "-Djava.library.path=${natives_directory}",
+                "-cp",
+                "${classpath}"
+            ]
+        }))
+        .unwrap();
+        let args = Args::Arguments(&arguments);
+
+        let features = FeatureSet {
+            has_custom_resolution: true,
+            ..FeatureSet::default()
+        };
+        let context = RuleContext::new(Platform::new(OsName::Linux, Arch::X86_64, None), features);
+
+        assert_eq!(
+            game_arguments(&args, &placeholders(), &context),
+            ["--username", "Steve", "--width", "${resolution_width}"]
+        );
+        assert_eq!(
+            jvm_arguments(&args, &placeholders(), &context),
+            [
+                "-Dos.name=Linux Mint",
+                "-Xss1M",
+                "-Djava.library.path=/natives dir",
+                "-cp",
+                "a.jar:b.jar"
+            ]
+        );
     }
 }
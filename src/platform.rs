@@ -0,0 +1,217 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParsePlatformError {
+    UnknownOs,
+    UnknownArch,
+}
+
+impl Display for ParsePlatformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnknownOs => "Unknown OS name",
+            Self::UnknownArch => "Unknown architecture",
+        })
+    }
+}
+
+impl Error for ParsePlatformError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsName {
+    Windows,
+    Osx,
+    Linux,
+}
+
+impl OsName {
+    /// The name the way manifests spell it
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Windows => "windows",
+            Self::Osx => "osx",
+            Self::Linux => "linux",
+        }
+    }
+}
+
+impl FromStr for OsName {
+    type Err = ParsePlatformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "windows" => Ok(Self::Windows),
+            "osx" | "macos" => Ok(Self::Osx),
+            "linux" => Ok(Self::Linux),
+            _ => Err(ParsePlatformError::UnknownOs),
+        }
+    }
+}
+
+impl Display for OsName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm32,
+    Arm64,
+}
+
+impl Arch {
+    /// The name the way manifests spell it
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::X86 => "x86",
+            Self::X86_64 => "x86_64",
+            Self::Arm32 => "arm32",
+            Self::Arm64 => "arm64",
+        }
+    }
+
+    /// The pointer width, used for `${arch}` in natives classifiers
+    #[must_use]
+    pub const fn bits(self) -> &'static str {
+        match self {
+            Self::X86 | Self::Arm32 => "32",
+            Self::X86_64 | Self::Arm64 => "64",
+        }
+    }
+}
+
+impl FromStr for Arch {
+    type Err = ParsePlatformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x86" | "i386" | "i686" => Ok(Self::X86),
+            "x86_64" | "amd64" | "x64" => Ok(Self::X86_64),
+            "arm32" | "arm" => Ok(Self::Arm32),
+            "arm64" | "aarch64" => Ok(Self::Arm64),
+            _ => Err(ParsePlatformError::UnknownArch),
+        }
+    }
+}
+
+impl Display for Arch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The platform an instance is installed for. This is the host by default, but can be any other
+/// platform so that instances can be prepared or linted for other players.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    os: OsName,
+    arch: Arch,
+    /// Matches what Java reports as `os.version`, used by `os.version` rules
+    version: Option<String>,
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl Platform {
+    #[must_use]
+    pub const fn new(os: OsName, arch: Arch, version: Option<String>) -> Self {
+        Self { os, arch, version }
+    }
+
+    /// Detects the host platform. Detection only runs once, later calls return a copy.
+    ///
+    /// Unix-likes other than macOS are treated as linux, and unknown architectures as `x86_64`.
+    #[must_use]
+    pub fn current() -> Self {
+        static CURRENT: OnceLock<Platform> = OnceLock::new();
+
+        CURRENT.get_or_init(Self::detect).clone()
+    }
+
+    fn detect() -> Self {
+        let os = if cfg!(target_os = "windows") {
+            OsName::Windows
+        } else if cfg!(target_os = "macos") {
+            OsName::Osx
+        } else {
+            OsName::Linux
+        };
+
+        let arch = std::env::consts::ARCH.parse().unwrap_or(Arch::X86_64);
+
+        Self {
+            os,
+            arch,
+            version: host_os_version(),
+        }
+    }
+
+    /// The separator used between classpath entries
+    #[must_use]
+    pub const fn classpath_separator(&self) -> &'static str {
+        match self.os {
+            OsName::Windows => ";",
+            OsName::Osx | OsName::Linux => ":",
+        }
+    }
+}
+
+/// Getter methods
+impl Platform {
+    #[must_use]
+    pub const fn os(&self) -> OsName {
+        self.os
+    }
+
+    #[must_use]
+    pub const fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    #[must_use]
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+}
+
+/// The OS version, matching what Java reports as `os.version`
+fn host_os_version() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        // windows lies about its version unless the binary has a manifest, so only check for 10
+        winsafe::IsWindows10OrGreater()
+            .unwrap_or(false)
+            .then(|| "10.0".to_owned())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("sw_vers")
+            .arg("-productVersion")
+            .output()
+            .ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|version| version.trim().to_owned())
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|version| version.trim().to_owned())
+    }
+}
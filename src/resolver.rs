@@ -0,0 +1,322 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use itertools::Itertools;
+use tokio::fs;
+use tracing::debug;
+
+use crate::{
+    assets::{client, version},
+    merger::{MergableManifest, MergeReport},
+};
+
+#[derive(Debug)]
+pub enum ResolveError {
+    /// The version is neither in the versions directory nor in the version manifest
+    NotFound,
+    IoError,
+    ParseError,
+    DownloadError,
+    /// The `inheritsFrom` chain loops back onto itself
+    Cycle,
+    /// The merged manifest is missing required fields
+    InvalidManifest,
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotFound => "Could not find version",
+            Self::IoError => "Failed during IO task",
+            Self::ParseError => "Could not parse version manifest",
+            Self::DownloadError => "Could not download version manifest",
+            Self::Cycle => "Cycle detected in inheritsFrom chain",
+            Self::InvalidManifest => "Resolved manifest is invalid",
+        })
+    }
+}
+
+impl Error for ResolveError {}
+
+/// Resolves `inheritsFrom` chains (e.g. Fabric, Quilt or Forge profiles) into a single manifest.
+///
+/// Versions are looked up by id in `<versions_directory>/<id>/<id>.json` first. If they are not
+/// there and a version manifest was given, they are downloaded from Mojang instead.
+#[derive(Debug, Clone)]
+pub struct Resolver<'a> {
+    versions_directory: PathBuf,
+    version_manifest: Option<&'a version::Manifest>,
+}
+
+impl<'a> Resolver<'a> {
+    #[must_use]
+    pub const fn new(
+        versions_directory: PathBuf,
+        version_manifest: Option<&'a version::Manifest>,
+    ) -> Self {
+        Self {
+            versions_directory,
+            version_manifest,
+        }
+    }
+
+    /// Gets the path a version's manifest is expected to be at
+    #[must_use]
+    pub fn manifest_path(&self, id: &str) -> PathBuf {
+        self.versions_directory.join(id).join(format!("{id}.json"))
+    }
+
+    /// Whether the version can be found, either on disk or in the version manifest. This does
+    /// not check that the version parses
+    #[must_use]
+    pub fn can_resolve(&self, id: &str) -> bool {
+        self.manifest_path(id).exists()
+            || self
+                .version_manifest
+                .is_some_and(|manifest| manifest.version(id).is_some())
+    }
+
+    /// Resolves the version with the given id into a launchable manifest.
+    ///
+    /// # Errors
+    /// Errors if any version in the chain cannot be found or parsed, if the chain contains a
+    /// cycle, or if the merged manifest is missing required fields.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve(&self, id: &str) -> Result<client::Manifest, ResolveError> {
+        let (merged, report) = self.resolve_mergable(id).await?;
+
+        if !report.is_empty() {
+            debug!("Library conflicts while resolving {}: {:?}", id, report);
+        }
+
+        client::Manifest::try_from(merged)
+            .into_report()
+            .change_context(ResolveError::InvalidManifest)
+            .attach_printable_lazy(|| format!("While resolving version {id}"))
+    }
+
+    /// Walks the `inheritsFrom` chain of the given version, merging every parent into it.
+    ///
+    /// Also returns a report of every library dropped along the way.
+    ///
+    /// # Errors
+    /// Errors if any version in the chain cannot be found or parsed, or if the chain contains a
+    /// cycle.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve_mergable(
+        &self,
+        id: &str,
+    ) -> Result<(MergableManifest, MergeReport), ResolveError> {
+        let mut chain = vec![id.to_owned()];
+        let mut manifest = self.load(id).await?;
+        let mut report = MergeReport::default();
+
+        while let Some(parent_id) = manifest.inherits_from().map(ToOwned::to_owned) {
+            if chain.contains(&parent_id) {
+                chain.push(parent_id);
+
+                return Err(Report::new(ResolveError::Cycle))
+                    .attach_printable(format!("Chain: {}", chain.iter().join(" -> ")));
+            }
+
+            debug!("{} inherits from {}", chain.iter().join(" -> "), parent_id);
+            let parent = self.load(&parent_id).await?;
+            let grandparent_id = parent.inherits_from().map(ToOwned::to_owned);
+            chain.push(parent_id);
+
+            report.extend(manifest.merge_with(parent));
+            // the merged manifest continues from the parent, ending at the root's `None`
+            manifest.set_inherits_from(grandparent_id);
+        }
+
+        Ok((manifest, report))
+    }
+
+    async fn load(&self, id: &str) -> Result<MergableManifest, ResolveError> {
+        let path = self.manifest_path(id);
+
+        if path
+            .try_exists()
+            .into_report()
+            .change_context(ResolveError::IoError)?
+        {
+            return Self::load_from_disk(&path).await;
+        }
+
+        let version = self
+            .version_manifest
+            .and_then(|manifest| manifest.version(id))
+            .ok_or(ResolveError::NotFound)
+            .into_report()
+            .attach_printable_lazy(|| format!("Version {id} is not at {}", path.display()))?;
+
+        debug!("Downloading {} from the version manifest", id);
+
+        version
+            .download()
+            .await
+            .change_context(ResolveError::DownloadError)
+            .map(MergableManifest::from)
+    }
+
+    async fn load_from_disk(path: &Path) -> Result<MergableManifest, ResolveError> {
+        debug!("Loading {} from disk", path.display());
+
+        let contents = fs::read_to_string(path)
+            .await
+            .into_report()
+            .change_context(ResolveError::IoError)?;
+
+        serde_json::from_str(&contents)
+            .into_report()
+            .change_context(ResolveError::ParseError)
+            .attach_printable_lazy(|| format!("In {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::merger::ConversionError;
+
+    async fn write_versions(name: &str, versions: &[serde_json::Value]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("copper-{name}-{}", std::process::id()));
+
+        for version in versions {
+            let id = version["id"].as_str().unwrap();
+            fs::create_dir_all(dir.join(id)).await.unwrap();
+            fs::write(dir.join(id).join(format!("{id}.json")), version.to_string())
+                .await
+                .unwrap();
+        }
+
+        dir
+    }
+
+    #[tokio::test]
+    async fn two_levels() {
+        let dir = write_versions(
+            "resolver-two",
+            &[
+                json!({
+                    "id": "fabric-loader-1.20.1",
+                    "inheritsFrom": "1.20.1",
+                    "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+                    "libraries": [{
+                        "name": "net.fabricmc:fabric-loader:0.14.21",
+                        "url": "https://maven.fabricmc.net/"
+                    }]
+                }),
+                json!({
+                    "id": "1.20.1",
+                    "mainClass": "net.minecraft.client.main.Main",
+                    "libraries": [{ "name": "com.mojang:brigadier:1.1.8" }]
+                }),
+            ],
+        )
+        .await;
+
+        let (manifest, _) = Resolver::new(dir.clone(), None)
+            .resolve_mergable("fabric-loader-1.20.1")
+            .await
+            .unwrap();
+
+        assert_eq!(manifest.id(), Some("fabric-loader-1.20.1"));
+        assert_eq!(manifest.inherits_from(), None);
+        assert_eq!(manifest.libraries().len(), 2);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn three_levels() {
+        let dir = write_versions(
+            "resolver-three",
+            &[
+                json!({
+                    "id": "modpack",
+                    "inheritsFrom": "forge",
+                    "libraries": [{ "name": "a:pack:1" }]
+                }),
+                json!({
+                    "id": "forge",
+                    "inheritsFrom": "1.12.2",
+                    "libraries": [{ "name": "a:forge:1" }]
+                }),
+                json!({ "id": "1.12.2", "libraries": [{ "name": "a:vanilla:1" }] }),
+            ],
+        )
+        .await;
+
+        let (manifest, _) = Resolver::new(dir.clone(), None)
+            .resolve_mergable("modpack")
+            .await
+            .unwrap();
+
+        assert_eq!(manifest.inherits_from(), None);
+        assert_eq!(manifest.libraries().len(), 3);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cycle() {
+        let dir = write_versions(
+            "resolver-cycle",
+            &[
+                json!({ "id": "a", "inheritsFrom": "b" }),
+                json!({ "id": "b", "inheritsFrom": "c" }),
+                json!({ "id": "c", "inheritsFrom": "a" }),
+            ],
+        )
+        .await;
+
+        let error = Resolver::new(dir.clone(), None)
+            .resolve_mergable("a")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.current_context(), ResolveError::Cycle));
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_field() {
+        let dir = write_versions(
+            "resolver-missing",
+            &[
+                json!({
+                    "id": "forge-1.7.10",
+                    "inheritsFrom": "1.7.10",
+                    "minecraftArguments": "--username ${auth_player_name} --tweakClass cpw.mods.fml.common.launcher.FMLTweaker",
+                    "mainClass": "net.minecraft.launchwrapper.Launch"
+                }),
+                json!({ "id": "1.7.10", "assets": "1.7.10" }),
+            ],
+        )
+        .await;
+
+        let error = Resolver::new(dir.clone(), None)
+            .resolve("forge-1.7.10")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            ResolveError::InvalidManifest
+        ));
+        assert!(matches!(
+            error.downcast_ref::<ConversionError>(),
+            Some(ConversionError::MissingField("assetIndex"))
+        ));
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
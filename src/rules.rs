@@ -0,0 +1,266 @@
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    assets::client::{Action, Features, GameRule, Os, Rule},
+    platform::{Arch, OsName, Platform},
+};
+
+/// The outcome of evaluating a list of rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Disallow,
+}
+
+impl Decision {
+    #[must_use]
+    pub const fn is_allowed(self) -> bool {
+        matches!(self, Self::Allow)
+    }
+}
+
+impl From<&Action> for Decision {
+    fn from(action: &Action) -> Self {
+        match action {
+            Action::Allow => Self::Allow,
+            Action::Disallow => Self::Disallow,
+        }
+    }
+}
+
+/// The feature flags game arguments can be gated behind
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FeatureSet {
+    pub is_demo_user: bool,
+    pub has_custom_resolution: bool,
+    pub has_quick_plays_support: bool,
+    pub is_quick_play_singleplayer: bool,
+    pub is_quick_play_multiplayer: bool,
+    pub is_quick_play_realms: bool,
+}
+
+/// Everything a rule can be matched against
+#[derive(Debug, Clone, Default)]
+pub struct RuleContext {
+    platform: Platform,
+    features: FeatureSet,
+}
+
+impl RuleContext {
+    /// Creates a context for the given platform with the given features enabled
+    #[must_use]
+    pub const fn new(platform: Platform, features: FeatureSet) -> Self {
+        Self { platform, features }
+    }
+
+    /// Creates a context for the given platform with no features enabled
+    #[must_use]
+    pub fn for_platform(platform: Platform) -> Self {
+        Self::new(platform, FeatureSet::default())
+    }
+
+    #[must_use]
+    pub const fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    #[must_use]
+    pub const fn features(&self) -> &FeatureSet {
+        &self.features
+    }
+
+    fn matches_os(&self, os: &Os) -> bool {
+        // unknown names and architectures never match
+        let name = os.name().is_none_or(|name| {
+            name.parse::<OsName>()
+                .is_ok_and(|name| name == self.platform.os())
+        });
+
+        let arch = os.arch().is_none_or(|arch| {
+            arch.parse::<Arch>()
+                .is_ok_and(|arch| arch == self.platform.arch())
+        });
+
+        let version = os.version().is_none_or(|pattern| {
+            let Some(version) = self.platform.version() else {
+                return false;
+            };
+
+            match Regex::new(pattern) {
+                Ok(regex) => regex.is_match(version),
+                Err(error) => {
+                    warn!("Invalid os version pattern {}: {}", pattern, error);
+                    false
+                }
+            }
+        });
+
+        name && arch && version
+    }
+
+    fn matches_features(&self, features: &Features) -> bool {
+        let checks = [
+            (features.demo_user(), self.features.is_demo_user),
+            (
+                features.custom_resolution(),
+                self.features.has_custom_resolution,
+            ),
+            (
+                features.quick_plays_support(),
+                self.features.has_quick_plays_support,
+            ),
+            (
+                features.quick_play_singleplayer(),
+                self.features.is_quick_play_singleplayer,
+            ),
+            (
+                features.quick_play_multiplayer(),
+                self.features.is_quick_play_multiplayer,
+            ),
+            (
+                features.quick_play_realms(),
+                self.features.is_quick_play_realms,
+            ),
+        ];
+
+        checks
+            .into_iter()
+            .all(|(wanted, actual)| wanted.is_none_or(|wanted| wanted == actual))
+    }
+}
+
+/// A single rule that can be evaluated by [`evaluate`]
+pub trait Condition {
+    fn action(&self) -> &Action;
+
+    /// Whether the rule applies in the given context. This says nothing about the action.
+    fn matches(&self, context: &RuleContext) -> bool;
+}
+
+impl Condition for Rule {
+    fn action(&self) -> &Action {
+        Self::action(self)
+    }
+
+    fn matches(&self, context: &RuleContext) -> bool {
+        let os = self.os().is_none_or(|os| context.matches_os(os));
+        let features = self
+            .features()
+            .is_none_or(|features| context.matches_features(features));
+
+        os && features
+    }
+}
+
+impl Condition for GameRule {
+    fn action(&self) -> &Action {
+        Self::action(self)
+    }
+
+    fn matches(&self, context: &RuleContext) -> bool {
+        context.matches_features(self.features())
+    }
+}
+
+/// Evaluates a list of rules the same way the vanilla launcher does.
+///
+/// No rules at all means the item is allowed. Otherwise the item is disallowed unless a rule
+/// matches, and the last matching rule decides.
+pub fn evaluate<'a, C: Condition + 'a>(
+    rules: impl IntoIterator<Item = &'a C>,
+    context: &RuleContext,
+) -> Decision {
+    let mut rules = rules.into_iter().peekable();
+
+    if rules.peek().is_none() {
+        return Decision::Allow;
+    }
+
+    rules
+        .filter(|rule| rule.matches(context))
+        .last()
+        .map_or(Decision::Disallow, |rule| Decision::from(rule.action()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(rules: &serde_json::Value) -> Vec<Rule> {
+        serde_json::from_value(rules.clone()).unwrap()
+    }
+
+    fn context(os: &str, arch: &str, version: &str) -> RuleContext {
+        RuleContext::for_platform(Platform::new(
+            os.parse().unwrap(),
+            arch.parse().unwrap(),
+            Some(version.to_owned()),
+        ))
+    }
+
+    #[test]
+    fn no_rules_allow() {
+        assert_eq!(
+            evaluate(&rules(&json!([])), &context("linux", "x86_64", "6.1")),
+            Decision::Allow
+        );
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        // from 1.7.10's lwjgl
+        let rules = rules(&json!([
+            { "action": "allow" },
+            { "action": "disallow", "os": { "name": "osx" } }
+        ]));
+
+        assert_eq!(
+            evaluate(&rules, &context("linux", "x86_64", "6.1")),
+            Decision::Allow
+        );
+        assert_eq!(
+            evaluate(&rules, &context("osx", "x86_64", "10.15.7")),
+            Decision::Disallow
+        );
+    }
+
+    #[test]
+    fn no_matching_rule_disallows() {
+        let rules = rules(&json!([{ "action": "allow", "os": { "name": "osx" } }]));
+
+        assert_eq!(
+            evaluate(&rules, &context("windows", "x86_64", "10.0")),
+            Decision::Disallow
+        );
+    }
+
+    #[test]
+    fn os_version_and_arch() {
+        let version = rules(&json!([
+            { "action": "allow", "os": { "name": "windows", "version": "^10\\." } }
+        ]));
+        let arch = rules(&json!([{ "action": "allow", "os": { "arch": "x86" } }]));
+
+        assert!(evaluate(&version, &context("windows", "x86_64", "10.0")).is_allowed());
+        assert!(!evaluate(&version, &context("windows", "x86_64", "6.1")).is_allowed());
+        assert!(evaluate(&arch, &context("windows", "x86", "10.0")).is_allowed());
+        assert!(!evaluate(&arch, &context("linux", "arm64", "6.1")).is_allowed());
+    }
+
+    #[test]
+    fn features() {
+        let rules: Vec<GameRule> = serde_json::from_value(json!([
+            { "action": "allow", "features": { "has_custom_resolution": true } }
+        ]))
+        .unwrap();
+
+        let mut context = context("linux", "x86_64", "6.1");
+        assert!(!evaluate(&rules, &context).is_allowed());
+
+        context.features.has_custom_resolution = true;
+        assert!(evaluate(&rules, &context).is_allowed());
+    }
+}
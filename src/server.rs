@@ -0,0 +1,567 @@
+use derive_builder::Builder;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter, Write as _},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+};
+use tokio::{
+    fs,
+    io::{AsyncWriteExt, BufReader},
+    process::{ChildStderr, ChildStdin, ChildStdout, Command},
+    task::JoinHandle,
+};
+use tracing::debug;
+
+use crate::{assets::client, launcher::RamSize};
+
+const EULA_FILE: &str = "eula.txt";
+const PROPERTIES_FILE: &str = "server.properties";
+
+#[derive(Debug)]
+pub enum ServerError {
+    DownloadError,
+    IoError,
+    /// The EULA has not been accepted, so the server would exit straight away
+    EulaNotAccepted,
+    ProcessError,
+    CannotGetStdin,
+    CannotGetStdout,
+    CannotGetStderr,
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DownloadError => "Could not download server jar",
+            Self::IoError => "Failed during IO task",
+            Self::EulaNotAccepted => "The Minecraft EULA has not been accepted",
+            Self::ProcessError => "Could not spawn server process",
+            Self::CannotGetStdin => "Could not get stdin",
+            Self::CannotGetStdout => "Could not get stdout",
+            Self::CannotGetStderr => "Could not get stderr",
+        })
+    }
+}
+
+impl Error for ServerError {}
+
+/// Whether `eula.txt` in the server directory accepts the EULA
+pub async fn eula_accepted(server_directory: &Path) -> bool {
+    fs::read_to_string(server_directory.join(EULA_FILE))
+        .await
+        .is_ok_and(|eula| {
+            eula.lines()
+                .any(|line| line.trim().eq_ignore_ascii_case("eula=true"))
+        })
+}
+
+/// Writes an `eula.txt` accepting the EULA (<https://aka.ms/MinecraftEULA>) into the server
+/// directory. Only call this once the user has agreed to it
+///
+/// # Errors
+/// Errors if the file cannot be written
+pub async fn accept_eula(server_directory: &Path) -> Result<(), ServerError> {
+    fs::create_dir_all(server_directory)
+        .await
+        .into_report()
+        .change_context(ServerError::IoError)?;
+
+    fs::write(
+        server_directory.join(EULA_FILE),
+        "#By changing the setting below to TRUE you are indicating your agreement to our EULA \
+         (https://aka.ms/MinecraftEULA).\neula=true\n",
+    )
+    .await
+    .into_report()
+    .change_context(ServerError::IoError)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PropertyLine {
+    /// Comments and blank lines, kept so saving does not lose them
+    Other(String),
+    Property(String, String),
+}
+
+/// `server.properties`, with typed accessors for the common keys. Unknown keys, comments and the
+/// order of the file are kept as is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerProperties {
+    lines: Vec<PropertyLine>,
+}
+
+impl ServerProperties {
+    /// Parses the contents of a `server.properties` file
+    #[must_use]
+    pub fn parse(properties: &str) -> Self {
+        let lines = properties
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+
+                if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+                    return PropertyLine::Other(line.to_owned());
+                }
+
+                let (key, value) = split_property(trimmed);
+                PropertyLine::Property(unescape(key), unescape(value))
+            })
+            .collect();
+
+        Self { lines }
+    }
+
+    /// Loads `server.properties` from the server directory. A missing file gives empty
+    /// properties, the server fills in the defaults on its first start
+    ///
+    /// # Errors
+    /// Errors if the file exists but cannot be read
+    pub async fn load(server_directory: &Path) -> Result<Self, ServerError> {
+        let path = server_directory.join(PROPERTIES_FILE);
+
+        if !fs::try_exists(&path)
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)?
+        {
+            return Ok(Self::default());
+        }
+
+        let properties = fs::read_to_string(&path)
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)?;
+
+        Ok(Self::parse(&properties))
+    }
+
+    /// Saves `server.properties` into the server directory
+    ///
+    /// # Errors
+    /// Errors if the file cannot be written
+    pub async fn save(&self, server_directory: &Path) -> Result<(), ServerError> {
+        fs::create_dir_all(server_directory)
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)?;
+
+        fs::write(server_directory.join(PROPERTIES_FILE), self.to_string())
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| match line {
+            PropertyLine::Property(k, value) if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Sets a property, keeping its position if it already exists
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+
+        let existing = self.lines.iter_mut().find_map(|line| match line {
+            PropertyLine::Property(k, value) if k == key => Some(value),
+            _ => None,
+        });
+
+        match existing {
+            Some(existing) => *existing = value,
+            None => self
+                .lines
+                .push(PropertyLine::Property(key.to_owned(), value)),
+        }
+    }
+
+    fn get_parsed<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+}
+
+/// Typed accessors
+impl ServerProperties {
+    #[must_use]
+    pub fn server_port(&self) -> Option<u16> {
+        self.get_parsed("server-port")
+    }
+
+    pub fn set_server_port(&mut self, port: u16) {
+        self.set("server-port", port.to_string());
+    }
+
+    #[must_use]
+    pub fn motd(&self) -> Option<&str> {
+        self.get("motd")
+    }
+
+    pub fn set_motd(&mut self, motd: &str) {
+        self.set("motd", motd);
+    }
+
+    #[must_use]
+    pub fn max_players(&self) -> Option<u32> {
+        self.get_parsed("max-players")
+    }
+
+    pub fn set_max_players(&mut self, max_players: u32) {
+        self.set("max-players", max_players.to_string());
+    }
+
+    #[must_use]
+    pub fn online_mode(&self) -> Option<bool> {
+        self.get_parsed("online-mode")
+    }
+
+    pub fn set_online_mode(&mut self, online_mode: bool) {
+        self.set("online-mode", online_mode.to_string());
+    }
+
+    #[must_use]
+    pub fn level_name(&self) -> Option<&str> {
+        self.get("level-name")
+    }
+
+    pub fn set_level_name(&mut self, level_name: &str) {
+        self.set("level-name", level_name);
+    }
+
+    #[must_use]
+    pub fn level_seed(&self) -> Option<&str> {
+        self.get("level-seed")
+    }
+
+    pub fn set_level_seed(&mut self, level_seed: &str) {
+        self.set("level-seed", level_seed);
+    }
+
+    /// The default gamemode, e.g `survival`. Older versions use numbers instead
+    #[must_use]
+    pub fn gamemode(&self) -> Option<&str> {
+        self.get("gamemode")
+    }
+
+    pub fn set_gamemode(&mut self, gamemode: &str) {
+        self.set("gamemode", gamemode);
+    }
+
+    /// The difficulty, e.g `easy`. Older versions use numbers instead
+    #[must_use]
+    pub fn difficulty(&self) -> Option<&str> {
+        self.get("difficulty")
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: &str) {
+        self.set("difficulty", difficulty);
+    }
+
+    #[must_use]
+    pub fn pvp(&self) -> Option<bool> {
+        self.get_parsed("pvp")
+    }
+
+    pub fn set_pvp(&mut self, pvp: bool) {
+        self.set("pvp", pvp.to_string());
+    }
+
+    #[must_use]
+    pub fn white_list(&self) -> Option<bool> {
+        self.get_parsed("white-list")
+    }
+
+    pub fn set_white_list(&mut self, white_list: bool) {
+        self.set("white-list", white_list.to_string());
+    }
+}
+
+impl Display for ServerProperties {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                PropertyLine::Other(line) => writeln!(f, "{line}")?,
+                PropertyLine::Property(key, value) => {
+                    writeln!(f, "{}={}", escape(key, true), escape(value, false))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a line at the first unescaped `=` or `:`, the way Java's `Properties` does
+fn split_property(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '=' | ':' if !escaped => {
+                return (line[..index].trim_end(), line[index + 1..].trim_start());
+            }
+            _ => escaped = false,
+        }
+    }
+
+    (line.trim_end(), "")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('u') => {
+                let code = chars.by_ref().take(4).collect::<String>();
+
+                if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    result.push(c);
+                }
+            }
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+
+    result
+}
+
+/// Escapes a key or value. Non-ASCII characters are written as `\uXXXX`, as older servers read
+/// the file as ISO-8859-1
+fn escape(value: &str, is_key: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            '=' | ':' if is_key => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' ' if is_key || index == 0 => result.push_str("\\ "),
+            c if c.is_ascii() => result.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    let _ = write!(result, "\\u{unit:04X}");
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// The running server
+pub struct ServerProcess {
+    /// Console input, see [`ServerProcess::send_command`]
+    pub stdin: ChildStdin,
+    pub stdout: BufReader<ChildStdout>,
+    pub stderr: BufReader<ChildStderr>,
+    pub exit_handle: JoinHandle<Option<ExitStatus>>,
+}
+
+impl ServerProcess {
+    /// Runs a console command, e.g `stop` or `op player`
+    ///
+    /// # Errors
+    /// Errors if the server's stdin is closed
+    pub async fn send_command(&mut self, command: &str) -> Result<(), ServerError> {
+        self.stdin
+            .write_all(format!("{}\n", command.trim_end()).as_bytes())
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)?;
+
+        self.stdin
+            .flush()
+            .await
+            .into_report()
+            .change_context(ServerError::IoError)
+    }
+}
+
+/// Downloads and runs a dedicated server
+#[derive(Debug, Builder)]
+#[builder(setter(into))]
+pub struct ServerLauncher {
+    /// The directory the server runs in, with its worlds, `eula.txt` and `server.properties`
+    server_directory: PathBuf,
+    /// The path to server.jar
+    jar_path: PathBuf,
+    /// The path to java
+    java_path: PathBuf,
+    /// The min/max amount of ram to use
+    ram_size: RamSize,
+    /// Extra JVM arguments, e.g GC flags
+    #[builder(default)]
+    jvm_arguments: Vec<String>,
+    /// Runs the server without its GUI. Defaults to true
+    #[builder(default = "true")]
+    nogui: bool,
+    /// Accepts the EULA when launching. Only set this once the user has agreed to it
+    #[builder(default)]
+    accept_eula: bool,
+}
+
+impl ServerLauncher {
+    /// Downloads the server jar of the manifest to the jar path, verifying its sha1
+    ///
+    /// # Errors
+    /// Errors if the download fails or the sha1 does not match
+    pub async fn download_jar(
+        &self,
+        manifest: &client::Manifest,
+        http_client: &reqwest::Client,
+    ) -> Result<(), ServerError> {
+        manifest
+            .downloads()
+            .server()
+            .download(&self.jar_path, http_client)
+            .await
+            .change_context(ServerError::DownloadError)
+    }
+
+    /// Launches the server, assuming the jar is downloaded
+    ///
+    /// # Errors
+    /// Errors if the EULA is not accepted, or the process cannot be spawned
+    #[tracing::instrument(skip(self))]
+    pub async fn launch(&self) -> Result<ServerProcess, ServerError> {
+        if self.accept_eula {
+            accept_eula(&self.server_directory).await?;
+        }
+
+        if !eula_accepted(&self.server_directory).await {
+            return Err(Report::new(ServerError::EulaNotAccepted));
+        }
+
+        let mut args = vec![
+            format!("-Xms{}", self.ram_size.min),
+            format!("-Xmx{}", self.ram_size.max),
+        ];
+        args.extend(self.jvm_arguments.iter().cloned());
+        args.push("-jar".to_owned());
+        args.push(self.jar_path.to_string_lossy().into_owned());
+
+        if self.nogui {
+            args.push("nogui".to_owned());
+        }
+
+        debug!("Server args: {:?}", args);
+
+        let mut process = Command::new(&self.java_path)
+            .current_dir(&self.server_directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .args(&args)
+            .spawn()
+            .into_report()
+            .change_context(ServerError::ProcessError)?;
+
+        let stdin = process.stdin.take().ok_or(ServerError::CannotGetStdin)?;
+        let stdout = process.stdout.take().ok_or(ServerError::CannotGetStdout)?;
+        let stderr = process.stderr.take().ok_or(ServerError::CannotGetStderr)?;
+
+        let exit = tokio::spawn(async move { process.wait().await.ok() });
+
+        Ok(ServerProcess {
+            stdin,
+            stdout: BufReader::new(stdout),
+            stderr: BufReader::new(stderr),
+            exit_handle: exit,
+        })
+    }
+}
+
+/// Getter methods
+impl ServerLauncher {
+    #[must_use]
+    pub const fn server_directory(&self) -> &PathBuf {
+        &self.server_directory
+    }
+
+    #[must_use]
+    pub const fn jar_path(&self) -> &PathBuf {
+        &self.jar_path
+    }
+
+    #[must_use]
+    pub const fn java_path(&self) -> &PathBuf {
+        &self.java_path
+    }
+
+    #[must_use]
+    pub const fn ram_size(&self) -> &RamSize {
+        &self.ram_size
+    }
+
+    #[must_use]
+    pub fn jvm_arguments(&self) -> &[String] {
+        &self.jvm_arguments
+    }
+
+    #[must_use]
+    pub const fn nogui(&self) -> bool {
+        self.nogui
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROPERTIES: &str = "\
+#Minecraft server properties
+#Sat Jul 29 12:00:00 UTC 2023
+enable-jmx-monitoring=false
+server-port=25565
+motd=A Minecraft Server
+online-mode=true
+";
+
+    #[test]
+    fn round_trip_keeps_unknown_keys() {
+        let properties = ServerProperties::parse(PROPERTIES);
+
+        assert_eq!(properties.to_string(), PROPERTIES);
+        assert_eq!(properties.get("enable-jmx-monitoring"), Some("false"));
+    }
+
+    #[test]
+    fn typed_accessors() {
+        let mut properties = ServerProperties::parse(PROPERTIES);
+
+        assert_eq!(properties.server_port(), Some(25565));
+        assert_eq!(properties.online_mode(), Some(true));
+
+        properties.set_server_port(25566);
+        properties.set_motd("§aTest server");
+        properties.set_max_players(4);
+
+        let saved = properties.to_string();
+        assert!(saved.contains("server-port=25566\nmotd=\\u00A7aTest server\n"));
+        assert!(saved.ends_with("max-players=4\n"));
+
+        let reparsed = ServerProperties::parse(&saved);
+        assert_eq!(reparsed.motd(), Some("§aTest server"));
+        assert_eq!(reparsed.max_players(), Some(4));
+    }
+}
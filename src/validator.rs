@@ -0,0 +1,439 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    assets::client::{
+        self, Arguments, AssetIndex, Classifiers, Downloads, Game, Jvm, Library, Logging, Os, Rule,
+        Value,
+    },
+    merger::MergableManifest,
+    parser::{unresolved_placeholders, PLACEHOLDERS},
+    platform::{Arch, OsName},
+    resolver::Resolver,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The manifest will probably still launch, but something is off
+    Warning,
+    /// The manifest cannot be launched as is
+    Error,
+}
+
+/// A problem found in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// Neither `arguments` nor `minecraftArguments` are present
+    MissingArguments,
+    /// A library has nothing to download, not even a maven name to fall back to Mojang's
+    /// repository with, e.g libraries generated by an installer. These have to already be on disk
+    NoDownloadSource { library: String },
+    /// An argument uses a placeholder the launcher does not fill in
+    UnknownPlaceholder { placeholder: String },
+    /// A sha1 that is not 40 hex characters
+    InvalidSha1 { location: String, sha1: String },
+    /// A rule matches an OS that does not exist, so it never matches
+    UnknownOs { name: String },
+    /// A rule matches an architecture that does not exist, so it never matches
+    UnknownArch { arch: String },
+    /// The version in `inheritsFrom` is neither on disk nor in the version manifest
+    UnresolvableInheritsFrom { id: String },
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub const fn severity(&self) -> Severity {
+        match self {
+            Self::MissingArguments
+            | Self::InvalidSha1 { .. }
+            | Self::UnresolvableInheritsFrom { .. } => Severity::Error,
+            Self::NoDownloadSource { .. }
+            | Self::UnknownPlaceholder { .. }
+            | Self::UnknownOs { .. }
+            | Self::UnknownArch { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingArguments => write!(f, "Manifest has no arguments"),
+            Self::NoDownloadSource { library } => {
+                write!(f, "Library {library} has no download source")
+            }
+            Self::UnknownPlaceholder { placeholder } => {
+                write!(f, "Unknown placeholder ${{{placeholder}}}")
+            }
+            Self::InvalidSha1 { location, sha1 } => {
+                write!(f, "Invalid sha1 {sha1:?} for {location}")
+            }
+            Self::UnknownOs { name } => write!(f, "Rule references unknown OS {name}"),
+            Self::UnknownArch { arch } => write!(f, "Rule references unknown architecture {arch}"),
+            Self::UnresolvableInheritsFrom { id } => {
+                write!(f, "Cannot find version {id} to inherit from")
+            }
+        }
+    }
+}
+
+/// The parts of a manifest that get validated, shared by full and mergable manifests
+struct ManifestParts<'m> {
+    asset_index: Option<&'m AssetIndex>,
+    downloads: Option<&'m Downloads>,
+    libraries: &'m [Library],
+    minecraft_arguments: Option<&'m str>,
+    arguments: Option<&'m Arguments>,
+    logging: Option<&'m Logging>,
+    inherits_from: Option<&'m str>,
+}
+
+/// Lints manifests, for example hand-edited ones, before they fail at launch
+#[derive(Debug, Clone, Default)]
+pub struct Validator<'a> {
+    resolver: Option<&'a Resolver<'a>>,
+}
+
+impl<'a> Validator<'a> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { resolver: None }
+    }
+
+    /// Uses the resolver to check `inheritsFrom`. Without one, `inheritsFrom` is not checked
+    #[must_use]
+    pub const fn with_resolver(mut self, resolver: &'a Resolver<'a>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    #[must_use]
+    pub fn validate(&self, manifest: &client::Manifest) -> Vec<Diagnostic> {
+        let inherits_from = manifest.inherits_from.as_ref().and_then(|id| id.to_str());
+
+        self.validate_parts(&ManifestParts {
+            asset_index: Some(&manifest.asset_index),
+            downloads: Some(&manifest.downloads),
+            libraries: &manifest.libraries,
+            minecraft_arguments: manifest.minecraft_arguments.as_deref(),
+            arguments: manifest.arguments.as_ref(),
+            logging: manifest.logging.as_ref(),
+            inherits_from,
+        })
+    }
+
+    /// Validates a manifest before it is merged. Arguments are only required if the manifest
+    /// does not inherit them
+    #[must_use]
+    pub fn validate_mergable(&self, manifest: &MergableManifest) -> Vec<Diagnostic> {
+        self.validate_parts(&ManifestParts {
+            asset_index: manifest.asset_index(),
+            downloads: manifest.downloads(),
+            libraries: manifest.libraries(),
+            minecraft_arguments: manifest.minecraft_arguments(),
+            arguments: manifest.arguments(),
+            logging: manifest.logging(),
+            inherits_from: manifest.inherits_from(),
+        })
+    }
+
+    fn validate_parts(&self, manifest: &ManifestParts) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let has_arguments = manifest.arguments.is_some() || manifest.minecraft_arguments.is_some();
+
+        if !has_arguments && manifest.inherits_from.is_none() {
+            diagnostics.push(Diagnostic::MissingArguments);
+        }
+
+        if let (Some(id), Some(resolver)) = (manifest.inherits_from, self.resolver) {
+            if !resolver.can_resolve(id) {
+                diagnostics.push(Diagnostic::UnresolvableInheritsFrom { id: id.to_owned() });
+            }
+        }
+
+        check_placeholders(manifest, &mut diagnostics);
+        check_sha1s(manifest, &mut diagnostics);
+        check_rules(manifest, &mut diagnostics);
+
+        for library in manifest.libraries {
+            let has_natives = library.natives().is_some()
+                || library
+                    .downloads()
+                    .and_then(|downloads| downloads.classifiers())
+                    .is_some();
+
+            let has_source = library
+                .artifact()
+                .is_some_and(|artifact| !artifact.url().is_empty());
+
+            if !has_source && !has_natives {
+                diagnostics.push(Diagnostic::NoDownloadSource {
+                    library: library.name().to_owned(),
+                });
+            }
+        }
+
+        diagnostics
+    }
+}
+
+fn check_placeholders(manifest: &ManifestParts, diagnostics: &mut Vec<Diagnostic>) {
+    let values = |value: &'_ Value| match value {
+        Value::String(value) => vec![value.clone()],
+        Value::StringArray(values) => values.clone(),
+    };
+
+    let mut arguments = manifest
+        .minecraft_arguments
+        .map(|arguments| vec![arguments.to_owned()])
+        .unwrap_or_default();
+
+    for argument in manifest
+        .arguments
+        .iter()
+        .flat_map(|arguments| arguments.game())
+    {
+        match argument {
+            Game::String(argument) => arguments.push(argument.clone()),
+            Game::GameClass(class) => arguments.extend(values(class.value())),
+        }
+    }
+
+    for argument in manifest
+        .arguments
+        .iter()
+        .flat_map(|arguments| arguments.jvm())
+    {
+        match argument {
+            Jvm::String(argument) => arguments.push(argument.clone()),
+            Jvm::Class(class) => arguments.extend(values(class.value())),
+        }
+    }
+
+    // every placeholder is still unresolved, as nothing was substituted
+    for placeholder in unresolved_placeholders(&arguments) {
+        if !PLACEHOLDERS.contains(&placeholder.as_str()) {
+            diagnostics.push(Diagnostic::UnknownPlaceholder { placeholder });
+        }
+    }
+}
+
+fn check_sha1s(manifest: &ManifestParts, diagnostics: &mut Vec<Diagnostic>) {
+    let mut sha1s = Vec::new();
+
+    if let Some(asset_index) = manifest.asset_index {
+        sha1s.push(("asset index".to_owned(), asset_index.sha1()));
+    }
+
+    if let Some(downloads) = manifest.downloads {
+        sha1s.push(("client".to_owned(), downloads.client().sha1()));
+        sha1s.push(("server".to_owned(), downloads.server().sha1()));
+
+        if let Some(mappings) = downloads.client_mappings() {
+            sha1s.push(("client mappings".to_owned(), mappings.sha1()));
+        }
+
+        if let Some(mappings) = downloads.server_mappings() {
+            sha1s.push(("server mappings".to_owned(), mappings.sha1()));
+        }
+    }
+
+    if let Some(logging) = manifest.logging {
+        sha1s.push(("logging config".to_owned(), logging.client().file().sha1()));
+    }
+
+    for library in manifest.libraries {
+        let name = || format!("library {}", library.name());
+
+        if let Some(sha1) = library.sha1() {
+            sha1s.push((name(), sha1));
+        }
+
+        let Some(downloads) = library.downloads() else {
+            continue;
+        };
+
+        let artifacts = downloads.artifact().into_iter().chain(
+            downloads
+                .classifiers()
+                .into_iter()
+                .flat_map(Classifiers::all),
+        );
+
+        for artifact in artifacts {
+            sha1s.push((name(), artifact.sha1()));
+        }
+    }
+
+    for (location, sha1) in sha1s {
+        // empty sha1s are used by installer generated files, and are never checked
+        let valid = sha1.len() == 40 && sha1.chars().all(|c| c.is_ascii_hexdigit());
+
+        if !sha1.is_empty() && !valid {
+            diagnostics.push(Diagnostic::InvalidSha1 {
+                location,
+                sha1: sha1.to_owned(),
+            });
+        }
+    }
+}
+
+fn check_rules(manifest: &ManifestParts, diagnostics: &mut Vec<Diagnostic>) {
+    let jvm_rules = manifest
+        .arguments
+        .iter()
+        .flat_map(|arguments| arguments.jvm())
+        .filter_map(|argument| match argument {
+            Jvm::Class(class) => Some(class.rules()),
+            Jvm::String(_) => None,
+        });
+
+    let library_rules = manifest
+        .libraries
+        .iter()
+        .filter_map(|library| library.rules());
+
+    let oses = jvm_rules
+        .chain(library_rules)
+        .flatten()
+        .filter_map(Rule::os);
+
+    for os in oses {
+        check_os(os, diagnostics);
+    }
+}
+
+fn check_os(os: &Os, diagnostics: &mut Vec<Diagnostic>) {
+    if let Some(name) = os.name() {
+        let known = name.parse::<OsName>().is_ok();
+        let diagnostic = Diagnostic::UnknownOs { name: name.clone() };
+
+        if !known && !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    if let Some(arch) = os.arch() {
+        let known = arch.parse::<Arch>().is_ok();
+        let diagnostic = Diagnostic::UnknownArch { arch: arch.clone() };
+
+        if !known && !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(overrides: &serde_json::Value) -> MergableManifest {
+        let mut manifest = json!({
+            "id": "test",
+            "minecraftArguments": "--username ${auth_player_name} --session ${auth_session}",
+            "libraries": [{
+                "name": "org.lwjgl.lwjgl:lwjgl:2.9.1",
+                "downloads": {
+                    "artifact": {
+                        "path": "org/lwjgl/lwjgl/lwjgl/2.9.1/lwjgl-2.9.1.jar",
+                        "sha1": "f58c5aabcef0e41e1d1e5bc5b3f5e1e2c7bd5e71",
+                        "size": 1,
+                        "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/lwjgl/2.9.1/lwjgl-2.9.1.jar"
+                    }
+                },
+                "rules": [{ "action": "allow", "os": { "name": "osx" } }]
+            }]
+        });
+
+        for (key, value) in overrides.as_object().unwrap() {
+            manifest[key] = value.clone();
+        }
+
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    #[test]
+    fn valid_manifest() {
+        assert_eq!(
+            Validator::new().validate_mergable(&manifest(&json!({}))),
+            []
+        );
+    }
+
+    #[test]
+    fn missing_arguments_unless_inherited() {
+        let missing = manifest(&json!({ "minecraftArguments": null }));
+        let inherited = manifest(&json!({ "minecraftArguments": null, "inheritsFrom": "1.7.10" }));
+
+        assert_eq!(
+            Validator::new().validate_mergable(&missing),
+            [Diagnostic::MissingArguments]
+        );
+        assert_eq!(Validator::new().validate_mergable(&inherited), []);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let manifest = manifest(&json!({
+            "minecraftArguments": "--username ${player_name}",
+            "libraries": [
+                {
+                    "name": "net.minecraftforge:forge:1.20.1-47.1.0:client",
+                    "downloads": {
+                        "artifact": { "path": "forge.jar", "sha1": "abc", "size": 1, "url": "" }
+                    },
+                    "rules": [{ "action": "allow", "os": { "name": "freebsd", "arch": "sparc" } }]
+                }
+            ]
+        }));
+
+        let diagnostics = Validator::new().validate_mergable(&manifest);
+
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::UnknownPlaceholder {
+                    placeholder: "player_name".to_owned()
+                },
+                Diagnostic::InvalidSha1 {
+                    location: "library net.minecraftforge:forge:1.20.1-47.1.0:client".to_owned(),
+                    sha1: "abc".to_owned()
+                },
+                Diagnostic::UnknownOs {
+                    name: "freebsd".to_owned()
+                },
+                Diagnostic::UnknownArch {
+                    arch: "sparc".to_owned()
+                },
+                Diagnostic::NoDownloadSource {
+                    library: "net.minecraftforge:forge:1.20.1-47.1.0:client".to_owned()
+                },
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity() == Severity::Error));
+    }
+
+    #[test]
+    fn name_only_library_falls_back_to_mojang() {
+        let manifest = manifest(&json!({
+            "libraries": [
+                { "name": "net.minecraftforge:forge:1.12.2-14.23.5.2860" },
+                {
+                    "name": "net.minecraft:launchwrapper:1.12",
+                    "url": "https://libraries.minecraft.net/"
+                },
+                { "name": "forge-installer-output" }
+            ]
+        }));
+
+        assert_eq!(
+            Validator::new().validate_mergable(&manifest),
+            [Diagnostic::NoDownloadSource {
+                library: "forge-installer-output".to_owned()
+            }]
+        );
+    }
+}